@@ -1,43 +1,89 @@
-use std::{env, fs};
-use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_yaml::Value;
+
+use super::{
+    content::ContentPredicate,
+    filter::{parse_duration, parse_size},
+    migration::{migrate, CONFIG_VERSION},
+    pattern::NamePattern,
+    presets::Preset,
+    remover::DeleteMode,
+    resolver::Resolver,
+};
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
-struct Checker {
-    pub target: String,
-    contains_files_dirs: Vec<String>,
-    excludes_files_dirs: Vec<String>,
+#[serde(deny_unknown_fields)]
+pub struct Checker {
+    /// Every dir matching any of them is a target, like `node_modules`, `.next` and `dist` of a Node project.
+    pub targets: Vec<NamePattern>,
+    contains_files_dirs: Vec<NamePattern>,
+    excludes_files_dirs: Vec<NamePattern>,
+    /// Conditions on the content of the files, checked after the names. All of them should be satisfied.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    contains_content: Vec<ContentPredicate>,
+    /// Finds the build dir which is redirected out of the project, see `Resolver`. It is a target besides the `targets`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resolver: Option<Resolver>,
+    /// The parts of a target which can be removed alone from the detail view, like the incremental caches of `target`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sub_targets: Vec<SubTarget>,
     pub prefix: String,
 }
-impl Checker {
-    pub fn check(&self, dirs: &Vec<String>, files: &Vec<String>) -> bool {
-        if dirs.contains(&self.target) == false {
-            return false;
+
+/// A named part of a target. `path` is a glob relative to the target dir, like `*/incremental` or `.cache`, which can match many dirs.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct SubTarget {
+    pub name: String,
+    pub path: String,
+}
+
+impl SubTarget {
+    /// The dirs matched in the target. The ones out of the target, by `..` or a symlink, are never matched.
+    pub fn find(&self, target: &Path) -> Vec<PathBuf> {
+        if Path::new(&self.path).components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return vec![];
         }
-        //There is any file of contains_files_dirs NOT in the dir, return false.
+        let (Ok(root), Ok(paths)) = (fs::canonicalize(target), glob::glob(&(glob::Pattern::escape(&target.to_string_lossy()) + "/" + &self.path))) else {
+            return vec![];
+        };
+        paths
+            .flatten()
+            .filter(|p| p.symlink_metadata().is_ok_and(|m| m.is_dir()) && fs::canonicalize(p).is_ok_and(|p| p.starts_with(&root) && p != root))
+            .collect()
+    }
+}
+impl Checker {
+    pub fn new(targets: Vec<NamePattern>, contains_files_dirs: Vec<NamePattern>, excludes_files_dirs: Vec<NamePattern>, prefix: &str) -> Self {
+        Checker { targets, contains_files_dirs, excludes_files_dirs, contains_content: vec![], resolver: None, sub_targets: vec![], prefix: prefix.to_owned() }
+    }
+    /// Returns the paths of the target dirs if the dir is valid. `root` is the path searched.
+    pub fn check(&self, root: &Path, dir: &Path, dirs: &[String], files: &[String]) -> Vec<PathBuf> {
+        //There is any file of contains_files_dirs NOT in the dir, return None.
         for file in &self.contains_files_dirs {
-            let mut res = false;
-            if dirs.contains(file) == true {
-                res = true
-            } else if files.contains(file) == true {
-                res = true
-            }
-            if res == false {
-                return false;
+            if file.find(dirs).is_none() && file.find(files).is_none() {
+                return vec![];
             }
         }
-        //There is any file of excludes_files_dirs in the dir, return false.
+        //There is any file of excludes_files_dirs in the dir, return None.
         for file in &self.excludes_files_dirs {
-            let mut res = true;
-            if dirs.contains(file) == true {
-                res = false
-            } else if files.contains(file) == true {
-                res = false
-            }
-            if res == false {
-                return false;
+            if file.find(dirs).is_some() || file.find(files).is_some() {
+                return vec![];
             }
         }
-        true
+        let mut targets: Vec<PathBuf> = dirs.iter().filter(|d| self.targets.iter().any(|target| target.matches(d))).map(|d| dir.join(d)).collect();
+        //Read the files only if there can be any target.
+        if (targets.is_empty() && self.resolver.is_none()) || !self.contains_content.iter().all(|predicate| predicate.matches(dir)) {
+            return vec![];
+        }
+        if let Some(resolved) = self.resolver.and_then(|resolver| resolver.resolve(dir, root)) {
+            targets.push(resolved);
+        }
+        targets
     }
 }
 
@@ -52,27 +98,244 @@ pub fn get_default_config_path() -> Option<String> {
     None
 }
 
+/// `$XDG_CONFIG_HOME/kmall/config.yaml`, or `~/.config/kmall/config.yaml` if it is not set.
+pub fn get_xdg_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("kmall").join("config.yaml"))
+}
+
+/**
+ Where the config files can be, from the base layer to the one with the highest priority:
+ the file next to the executable (for compatibility), `$XDG_CONFIG_HOME/kmall/config.yaml`, `$KMALL_CONFIG` and `--config`.
+ */
+pub fn get_config_chain(explicit: Option<&Path>) -> Vec<PathBuf> {
+    let mut chain: Vec<PathBuf> = vec![];
+    let candidates = get_default_config_path()
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(get_xdg_config_path())
+        .chain(env::var_os("KMALL_CONFIG").filter(|p| !p.is_empty()).map(PathBuf::from))
+        .chain(explicit.map(Path::to_path_buf));
+    for path in candidates {
+        chain.retain(|p| p != &path);
+        chain.push(path);
+    }
+    chain
+}
+
+/// Maps are merged key by key and lists are appended, so a layer can add rules to the base. Other values are replaced.
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Mapping(base), Value::Mapping(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(layer)) => {
+            for value in layer {
+                if !base.contains(&value) {
+                    base.push(value);
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(default)]
+pub struct ConfirmConfig {
+    /// Ask before removing a dir.
+    pub enabled: bool,
+    /// The dir name should be typed to remove a dir not smaller than this size. Can be bytes or a size like "1G".
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub type_name_above: Option<u64>,
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        ConfirmConfig { enabled: true, type_name_above: Some(1024 * 1024 * 1024) }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SizeValue {
+    Bytes(u64),
+    Text(String),
+}
+
+fn deserialize_size<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    match Option::<SizeValue>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(SizeValue::Bytes(size)) => Ok(Some(size)),
+        Some(SizeValue::Text(text)) => parse_size(&text).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(default)]
+pub struct StalenessConfig {
+    /// Globs of the files and dirs skipped when finding the last modified time of a project, matching the name or the path relative to the project.
+    pub ignore: Vec<String>,
+    /// The levels of the dirs under the project which are read, so a project at the top of a large dir does not read all of it.
+    pub max_depth: usize,
+    /// The files and dirs read at most for a project.
+    pub max_entries: usize,
+}
+
+impl Default for StalenessConfig {
+    fn default() -> Self {
+        StalenessConfig { ignore: vec![".git".to_owned(), "node_modules".to_owned(), "target".to_owned()], max_depth: 8, max_entries: 100_000 }
+    }
+}
+
+impl StalenessConfig {
+    /// The invalid globs are skipped.
+    pub fn ignore_patterns(&self) -> Vec<glob::Pattern> {
+        self.ignore.iter().filter_map(|p| glob::Pattern::new(p).ok()).collect()
+    }
+}
+
+/// What to do with the targets in an active git repository.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ActiveRepo {
+    /// Only show the markers.
+    #[default]
+    None,
+    /// Show them, but they can not be removed.
+    Protect,
+    /// Do not show them.
+    Hide,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(default)]
+pub struct GitConfig {
+    /// A repository is active if it has uncommitted changes, or HEAD moved within this duration. A duration like "14d".
+    #[serde(deserialize_with = "deserialize_duration", serialize_with = "serialize_duration")]
+    pub active_within: Duration,
+    pub active: ActiveRepo,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        GitConfig { active_within: Duration::from_secs(60 * 60 * 24 * 14), active: ActiveRepo::None }
+    }
+}
+
+fn deserialize_duration<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    parse_duration(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn serialize_duration<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    let seconds = duration.as_secs();
+    if seconds.is_multiple_of(60 * 60 * 24) {
+        serializer.serialize_str(&format!("{}d", seconds / (60 * 60 * 24)))
+    } else {
+        serializer.serialize_str(&format!("{}s", seconds))
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct ConfigChecker {
+    /// See `CONFIG_VERSION`. Older configs are migrated before loading.
+    version: u64,
     includes: Vec<Checker>,
     shallow: Vec<String>,
+    /// The built-in rules after `includes`, see `Preset`.
+    #[serde(default)]
+    presets: Vec<Preset>,
+    #[serde(default)]
+    pub delete_mode: DeleteMode,
+    #[serde(default)]
+    pub confirm: ConfirmConfig,
+    #[serde(default)]
+    pub staleness: StalenessConfig,
+    #[serde(default)]
+    pub git: GitConfig,
+    /// The number of the threads calculating the sizes, 0 for the number of CPUs.
+    #[serde(default)]
+    pub size_threads: usize,
+    /// See `Scope`, the command line overrides `max_depth` and adds to `exclude`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub global_gitignore: bool,
+    /// See `Scope`, the command line can only turn them on.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub follow_symlinks: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub one_file_system: bool,
+    /// The files it is loaded from, the last one has the highest priority.
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
+}
+
+/**
+ Read the merged or migrated layers the same way as a file, so they are valid exactly when the files are.
+ `serde_yaml::from_value` is stricter, it does not read a plain scalar like `prefix: 5` as a string.
+ */
+fn from_layer<T: DeserializeOwned>(layer: &Value) -> Result<T, serde_yaml::Error> {
+    serde_yaml::from_str(&serde_yaml::to_string(layer)?)
+}
+
+/// What a single config file can contain. Every key is optional, because the others can be in another layer.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+#[allow(dead_code)]
+struct ConfigLayer {
+    version: Option<u64>,
+    includes: Option<Vec<Checker>>,
+    shallow: Option<Vec<String>>,
+    presets: Option<Vec<Preset>>,
+    delete_mode: Option<DeleteMode>,
+    confirm: Option<ConfirmConfig>,
+    staleness: Option<StalenessConfig>,
+    git: Option<GitConfig>,
+    size_threads: Option<usize>,
+    max_depth: Option<usize>,
+    exclude: Option<Vec<String>>,
+    global_gitignore: Option<bool>,
+    follow_symlinks: Option<bool>,
+    one_file_system: Option<bool>,
 }
 
 impl ConfigChecker {
     /**
      with Emoji will make some errors when calculating the width. So do not use any Emoji.
      */
-    pub fn init(with_emoji: bool) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    pub fn init(with_emoji: bool, path: &Path) -> Result<(), Box<dyn std::error::Error + 'static>> {
         let mut checker_npm = Checker {
-            target: "node_modules".to_owned(),
-            contains_files_dirs: vec!["package.json".to_owned()],
+            targets: vec![NamePattern::exact("node_modules")],
+            contains_files_dirs: vec![NamePattern::exact("package.json")],
             excludes_files_dirs: vec![],
+            contains_content: vec![],
+            resolver: None,
+            sub_targets: vec![SubTarget { name: "cache".to_owned(), path: ".cache".to_owned() }],
             prefix: "[Node]".to_owned(),
         };
         let mut checker_rust = Checker {
-            target: "target".to_owned(),
-            contains_files_dirs: vec!["Cargo.toml".to_owned()],
+            targets: vec![NamePattern::exact("target")],
+            contains_files_dirs: vec![NamePattern::exact("Cargo.toml")],
             excludes_files_dirs: vec![],
+            contains_content: vec![],
+            resolver: Some(Resolver::Cargo),
+            sub_targets: vec![
+                SubTarget { name: "incremental".to_owned(), path: "*/incremental".to_owned() },
+                SubTarget { name: "deps".to_owned(), path: "*/deps".to_owned() },
+            ],
             prefix: "[Rust]".to_owned(),
         };
         if with_emoji {
@@ -80,43 +343,137 @@ impl ConfigChecker {
             checker_rust.prefix = "[🦀️Rust]".to_owned();
         }
         let config = ConfigChecker {
+            version: CONFIG_VERSION,
             includes: vec![checker_npm, checker_rust],
             shallow: vec!["node_modules".to_owned()],
+            presets: vec![],
+            delete_mode: DeleteMode::Permanent,
+            confirm: ConfirmConfig::default(),
+            staleness: StalenessConfig::default(),
+            git: GitConfig::default(),
+            size_threads: 0,
+            max_depth: None,
+            exclude: vec![],
+            global_gitignore: false,
+            follow_symlinks: false,
+            one_file_system: false,
+            files: vec![],
         };
         let config_str = serde_yaml::to_string(&config)?;
-        if let Some(path) = get_default_config_path() {
-            fs::write(path, config_str)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
         }
+        fs::write(path, config_str)?;
         Ok(())
     }
-    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error + 'static>> {
-        let _s: String = String::from_utf8_lossy(&fs::read(path)?).parse()?;
-        let loader: ConfigChecker = serde_yaml::from_str(&_s)?;
+    /**
+     Load the files as layers, each one is merged over the ones before it. The files of older versions are migrated in memory first.
+     Each file is checked alone, so the error tells the file, the line, the column and the field, like `includes[1].prefix`.
+     A file of an older version is valid if it can be read after the migration, but the error is from the file as it is written.
+     The misspelled keys are errors too, like `sub_target` for `sub_targets`.
+     */
+    pub fn load(paths: &[PathBuf]) -> Result<Self, String> {
+        let mut merged = Value::Null;
+        for path in paths {
+            let _s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let mut layer: Value = serde_yaml::from_str(&_s).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let version = migrate(&mut layer).map_err(|e| format!("{}: {}", path.display(), e))?;
+            if let Err(e) = serde_yaml::from_str::<ConfigLayer>(&_s) {
+                if version == CONFIG_VERSION || from_layer::<ConfigLayer>(&layer).is_err() {
+                    return Err(format!("{}: {}", path.display(), e));
+                }
+            }
+            // An empty file.
+            if !layer.is_null() {
+                merge(&mut merged, layer);
+            }
+        }
+        let mut loader: ConfigChecker = from_layer(&merged).map_err(|e| format!("the merged config of {}: {}", paths.iter().map(|p| p.display().to_string()).collect::<Vec<String>>().join(", "), e))?;
+        loader.files = paths.to_vec();
+        for preset in loader.presets.clone() {
+            loader.includes.extend(preset.rules());
+            loader.shallow.extend(preset.shallow().iter().map(|s| s.to_string()));
+        }
         Ok(loader)
     }
-    pub fn load_init()->Option<Self>{
-        //try to load config, if None, create a default one and load it
-        if let Some(path) = get_default_config_path() {
-            if let Ok(_cl) = ConfigChecker::load(&path) {
-                return Some(_cl);
-            } else {
-                ConfigChecker::init(false).unwrap();
-                return Some(ConfigChecker::load(&path).unwrap())
+    /**
+     Load every config file of the chain. Only if there is none, create a default one with the highest priority and load it.
+     A file which can not be loaded is never overwritten, the error is returned instead.
+     */
+    pub fn load_init(explicit: Option<&Path>) -> Result<Self, String> {
+        // A file given by name must exist, so a typo is not replaced by another config silently.
+        let named = [(explicit.map(Path::to_path_buf), "--config"), (env::var_os("KMALL_CONFIG").filter(|p| !p.is_empty()).map(PathBuf::from), "KMALL_CONFIG")];
+        for (path, source) in named {
+            if let Some(path) = path.filter(|p| !p.exists()) {
+                return Err(format!("{}: the config given by {} does not exist", path.display(), source));
             }
-        } else {
-            return None;
         }
+        let files: Vec<PathBuf> = get_config_chain(explicit).into_iter().filter(|p| p.exists()).collect();
+        if !files.is_empty() {
+            return ConfigChecker::load(&files);
+        }
+        // Only created at the default places.
+        let path = get_xdg_config_path().or_else(|| get_default_config_path().map(PathBuf::from)).ok_or("can not find where to create the config")?;
+        ConfigChecker::init(false, &path).map_err(|e| format!("can not create {}: {}", path.display(), e))?;
+        ConfigChecker::load(&[path])
     }
     
     pub fn is_shallow(&self, file: &String) -> bool {
         self.shallow.contains(file)
     }
-    pub fn check(&self, dirs: &Vec<String>, files: &Vec<String>) -> Option<(String, String)> {
+    /// The rule and the path of every target of the dir under `root`. A target dir matched by many rules belongs to the first one.
+    pub fn check(&self, root: &Path, dir: &Path, dirs: &[String], files: &[String]) -> Vec<(&Checker, PathBuf)> {
+        let mut targets: Vec<(&Checker, PathBuf)> = vec![];
         for checker in &self.includes {
-            if checker.check(dirs, files) == true {
-                return Some((checker.prefix.to_owned(), checker.target.to_owned()));
+            for target in checker.check(root, dir, dirs, files) {
+                if !targets.iter().any(|(_, t)| t == &target) {
+                    targets.push((checker, target));
+                }
             }
         }
-        None
+        targets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(layers: &[&str]) -> Result<ConfigChecker, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = layers
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let path = dir.path().join(format!("{}.yaml", i));
+                fs::write(&path, text).unwrap();
+                path
+            })
+            .collect();
+        ConfigChecker::load(&paths)
+    }
+
+    const BASE: &str = "version: 6
+includes:
+- targets: [node_modules]
+  contains_files_dirs: [package.json]
+  excludes_files_dirs: []
+  prefix: '[Node]'
+shallow: []
+";
+
+    #[test]
+    fn plain_scalar_is_read_as_a_string() {
+        let loader = load(&[&BASE.replace("'[Node]'", "5"), "delete_mode: trash\n"]).unwrap();
+        assert_eq!(loader.includes[0].prefix, "5");
+        assert_eq!(loader.delete_mode, DeleteMode::Trash);
+    }
+
+    #[test]
+    fn misspelled_keys_are_named() {
+        let error = load(&[&BASE.replace("  prefix:", "  sub_target: []\n  prefix:")]).unwrap_err();
+        assert!(error.contains("unknown field `sub_target`") && error.contains("line 6"), "{}", error);
+        let error = load(&[BASE, "resolvr: cargo\n"]).unwrap_err();
+        assert!(error.contains("1.yaml") && error.contains("unknown field `resolvr`"), "{}", error);
     }
 }
@@ -0,0 +1,125 @@
+use std::{fs, path::Path};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The files larger than it are never read. Manifests are much smaller.
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/**
+ A condition on the content of a file in the project, relative to the dir containing the target. In the config it can be:
+ - a regex searched in the text, `^` and `$` match at the lines: `{ file: pom.xml, regex: "<packaging>jar</packaging>" }`
+ - a key path in a JSON, TOML or YAML file, which should exist: `{ file: Cargo.toml, key: workspace }`
+ - a key path with the value it should equal: `{ file: package.json, key: private, equals: true }`
+
+ The keys of a path are separated by `.`, and a number is an index of an array, like `workspaces.0`.
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(try_from = "ContentDef", into = "ContentDef")]
+pub struct ContentPredicate {
+    def: ContentDef,
+    matcher: Matcher,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct ContentDef {
+    file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    equals: Option<Value>,
+}
+
+#[derive(Clone, Debug)]
+enum Matcher {
+    Regex(Regex),
+    Key { format: Format, path: Vec<String>, equals: Option<Value> },
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ContentPredicate {
+    /// Whether the file in the dir satisfies it. A missing or unreadable file never does.
+    pub fn matches(&self, dir: &Path) -> bool {
+        let path = dir.join(&self.def.file);
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() && metadata.len() <= MAX_FILE_SIZE => {}
+            _ => return false,
+        }
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(_) => return false,
+        };
+        match &self.matcher {
+            Matcher::Regex(regex) => regex.is_match(&text),
+            Matcher::Key { format, path, equals } => {
+                let document: Option<Value> = match format {
+                    Format::Json => serde_json::from_str(&text).ok(),
+                    Format::Toml => toml::from_str(&text).ok(),
+                    Format::Yaml => serde_yaml::from_str(&text).ok(),
+                };
+                let value = document.as_ref().and_then(|d| find_key(d, path));
+                match (value, equals) {
+                    (Some(value), Some(equals)) => value == equals,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                }
+            }
+        }
+    }
+}
+
+fn find_key<'a>(document: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(document, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(array) => array.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+impl PartialEq for ContentPredicate {
+    fn eq(&self, other: &Self) -> bool {
+        self.def == other.def
+    }
+}
+
+impl TryFrom<ContentDef> for ContentPredicate {
+    type Error = String;
+
+    fn try_from(def: ContentDef) -> Result<Self, Self::Error> {
+        let matcher = match (&def.regex, &def.key) {
+            (Some(regex), None) => {
+                if def.equals.is_some() {
+                    return Err("`equals` can only be used with `key`".to_owned());
+                }
+                Matcher::Regex(RegexBuilder::new(regex).multi_line(true).build().map_err(|e| format!("invalid regex \"{}\": {}", regex, e))?)
+            }
+            (None, Some(key)) => {
+                let extension = Path::new(&def.file).extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+                let format = match extension.as_str() {
+                    "json" => Format::Json,
+                    "toml" => Format::Toml,
+                    "yaml" | "yml" => Format::Yaml,
+                    _ => return Err(format!("can not read keys of \"{}\", only JSON, TOML and YAML files are supported", def.file)),
+                };
+                Matcher::Key { format, path: key.split('.').map(str::to_owned).collect(), equals: def.equals.clone() }
+            }
+            _ => return Err(format!("the condition of \"{}\" should have either `regex` or `key`", def.file)),
+        };
+        Ok(ContentPredicate { def, matcher })
+    }
+}
+
+impl From<ContentPredicate> for ContentDef {
+    fn from(predicate: ContentPredicate) -> Self {
+        predicate.def
+    }
+}
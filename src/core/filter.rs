@@ -0,0 +1,101 @@
+use std::time::{Duration, SystemTime};
+
+use super::selector::SelectOptions;
+
+/// Conditions used by the subcommands to decide which of the searched targets should be handled.
+#[derive(Debug, Default)]
+pub struct Filter {
+    pub rules: Vec<String>,
+    pub min_size: Option<u64>,
+    pub older_than: Option<Duration>,
+    pub path_glob: Option<glob::Pattern>,
+}
+
+impl Filter {
+    pub fn matches(&self, option: &SelectOptions) -> bool {
+        if !self.rules.is_empty() && !self.rules.iter().any(|rule| match_rule(rule, option)) {
+            return false;
+        }
+        if let Some(min_size) = self.min_size {
+            match option.size {
+                Some(size) if size >= min_size => {}
+                _ => return false,
+            }
+        }
+        if let Some(older_than) = self.older_than {
+            let elapsed = option.modified.and_then(|m| SystemTime::now().duration_since(m).ok());
+            match elapsed {
+                Some(elapsed) if elapsed >= older_than => {}
+                _ => return false,
+            }
+        }
+        if let Some(pattern) = &self.path_glob {
+            if !pattern.matches(&option.path) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/**
+ A rule can be given by its prefix with or without the brackets (case insensitive), e.g. `[Node]` or `node`, or by its target name, e.g. `node_modules`.
+ */
+fn match_rule(rule: &str, option: &SelectOptions) -> bool {
+    let prefix = option.prefix.trim_start_matches('[').trim_end_matches(']');
+    rule == option.prefix || rule == option.target || rule.eq_ignore_ascii_case(prefix)
+}
+
+/// Parse a size like `512`, `100K`, `1.5G` or `20MB`. The units are based on 1024.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("invalid size: {}", s))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        "T" | "TB" => 1024 * 1024 * 1024 * 1024,
+        _ => return Err(format!("unknown size unit: {}", unit)),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// How long ago the time is, like `5 minutes ago` or `3 months ago`.
+pub fn format_age(time: SystemTime) -> String {
+    let seconds = match SystemTime::now().duration_since(time) {
+        Ok(elapsed) => elapsed.as_secs(),
+        Err(_) => return "just now".to_owned(),
+    };
+    let units = [(60 * 60 * 24 * 365, "year"), (60 * 60 * 24 * 30, "month"), (60 * 60 * 24 * 7, "week"), (60 * 60 * 24, "day"), (60 * 60, "hour"), (60, "minute")];
+    for (unit, name) in units {
+        let count = seconds / unit;
+        if count == 1 {
+            return format!("1 {} ago", name);
+        } else if count > 1 {
+            return format!("{} {}s ago", count, name);
+        }
+    }
+    "just now".to_owned()
+}
+
+/// Parse a duration like `90s`, `30m`, `12h`, `7d`, `2w`, `6mo` or `1y`. A month is 30 days and a year is 365 days.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().map_err(|_| format!("invalid duration: {}", s))?;
+    let seconds: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        "mo" => 60 * 60 * 24 * 30,
+        "y" => 60 * 60 * 24 * 365,
+        _ => return Err(format!("unknown duration unit: {}", unit)),
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
@@ -0,0 +1,281 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// What is known about the git repository containing a project, read from `.git` without running git.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GitStatus {
+    /// The last time HEAD moved (commit, checkout, merge...), from the reflog.
+    pub last_commit: Option<SystemTime>,
+    /// Some tracked files are modified or removed in the working tree.
+    pub dirty: bool,
+}
+
+impl GitStatus {
+    /// Dirty, or committed within the duration.
+    pub fn is_active(&self, within: Duration) -> bool {
+        if self.dirty {
+            return true;
+        }
+        match self.last_commit.and_then(|c| SystemTime::now().duration_since(c).ok()) {
+            Some(elapsed) => elapsed < within,
+            None => false,
+        }
+    }
+}
+
+/// Many projects can be in one repository, so the status is read only once for each repository.
+fn cache() -> &'static Mutex<HashMap<PathBuf, GitStatus>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, GitStatus>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The status of the repository containing the project, `None` if it is not in a repository.
+pub fn git_status(project: &Path) -> Option<GitStatus> {
+    let project = fs::canonicalize(project).ok()?;
+    let work_tree = project.ancestors().find(|dir| dir.join(".git").exists())?.to_path_buf();
+    if let Some(status) = cache().lock().unwrap().get(&work_tree) {
+        return Some(status.clone());
+    }
+    let git_dir = resolve_git_dir(&work_tree.join(".git"))?;
+    let status = GitStatus { last_commit: last_commit(&git_dir), dirty: is_dirty(&work_tree, &git_dir) };
+    cache().lock().unwrap().insert(work_tree, status.clone());
+    Some(status)
+}
+
+/// `.git` is a dir, or a file containing `gitdir: <path>` for worktrees and submodules.
+fn resolve_git_dir(dot_git: &Path) -> Option<PathBuf> {
+    if dot_git.is_dir() {
+        return Some(dot_git.to_path_buf());
+    }
+    let content = fs::read_to_string(dot_git).ok()?;
+    let git_dir = Path::new(content.trim().strip_prefix("gitdir:")?.trim());
+    Some(dot_git.parent()?.join(git_dir))
+}
+
+/**
+ The time of the last entry of the reflog of HEAD, like `<old> <new> Name <email> 1700000000 +0800\tcommit: message`.
+ Without the reflog, the modified time of the ref HEAD points to (or the index) is used.
+ */
+fn last_commit(git_dir: &Path) -> Option<SystemTime> {
+    if let Ok(log) = fs::read_to_string(git_dir.join("logs/HEAD")) {
+        let line = log.lines().rev().find(|l| !l.trim().is_empty())?;
+        let head = line.split('\t').next()?;
+        let mut fields = head.rsplitn(3, ' ');
+        let _zone = fields.next()?;
+        let seconds: u64 = fields.next()?.parse().ok()?;
+        return Some(UNIX_EPOCH + Duration::from_secs(seconds));
+    }
+    let common_dir = fs::read_to_string(git_dir.join("commondir")).map(|c| git_dir.join(c.trim())).unwrap_or_else(|_| git_dir.to_path_buf());
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let candidates = match head.trim().strip_prefix("ref:") {
+        Some(reference) => vec![common_dir.join(reference.trim()), common_dir.join("packed-refs")],
+        None => vec![git_dir.join("HEAD")],
+    };
+    candidates.into_iter().chain(std::iter::once(git_dir.join("index"))).find_map(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+}
+
+/**
+ Compare the size and modified time recorded in the index with the files in the working tree, like `git status` does before hashing.
+ Supports the index version 2, 3 and 4. Untracked files are not counted.
+ */
+fn is_dirty(work_tree: &Path, git_dir: &Path) -> bool {
+    let index = match fs::read(git_dir.join("index")) {
+        Ok(index) => index,
+        Err(_) => return false,
+    };
+    if index.len() < 12 || &index[0..4] != b"DIRC" {
+        return false;
+    }
+    let version = read_u32(&index, 4);
+    let count = read_u32(&index, 8) as usize;
+    let mut offset = 12;
+    let mut previous_path: Vec<u8> = vec![];
+    for _ in 0..count {
+        if offset + 62 > index.len() {
+            return false;
+        }
+        let mtime = read_u32(&index, offset + 8) as u64;
+        let mode = read_u32(&index, offset + 24);
+        let size = read_u32(&index, offset + 36);
+        let flags = u16::from_be_bytes([index[offset + 60], index[offset + 61]]);
+        let mut cursor = offset + 62;
+        let mut skip_worktree = false;
+        if version >= 3 && flags & 0x4000 != 0 {
+            if cursor + 2 > index.len() {
+                return false;
+            }
+            let extended = u16::from_be_bytes([index[cursor], index[cursor + 1]]);
+            skip_worktree = extended & 0x4000 != 0;
+            cursor += 2;
+        }
+        let path = if version >= 4 {
+            let (strip, read) = read_varint(&index[cursor..]);
+            cursor += read;
+            let end = match index[cursor..].iter().position(|b| *b == 0) {
+                Some(end) => cursor + end,
+                None => return false,
+            };
+            let mut path = previous_path[..previous_path.len().saturating_sub(strip)].to_vec();
+            path.extend_from_slice(&index[cursor..end]);
+            offset = end + 1;
+            path
+        } else {
+            let end = match index[cursor..].iter().position(|b| *b == 0) {
+                Some(end) => cursor + end,
+                None => return false,
+            };
+            let path = index[cursor..end].to_vec();
+            // The entry is padded with 1-8 NUL bytes to a multiple of 8 bytes.
+            offset += (end - offset + 8) & !7;
+            path
+        };
+        // Submodules (gitlinks) have nothing to compare.
+        if !skip_worktree && mode & 0o170000 != 0o160000 && is_changed(&work_tree.join(String::from_utf8_lossy(&path).as_ref()), size, mtime) {
+            return true;
+        }
+        previous_path = path;
+    }
+    false
+}
+
+fn is_changed(path: &Path, size: u32, mtime: u64) -> bool {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return true,
+    };
+    if metadata.len() as u32 != size {
+        return true;
+    }
+    match metadata.modified().ok().and_then(|m| m.duration_since(UNIX_EPOCH).ok()) {
+        Some(modified) => modified.as_secs() != mtime,
+        None => false,
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+/// The offset encoding of git: 7 bits each byte, adding 1 before shifting for the following bytes.
+fn read_varint(bytes: &[u8]) -> (usize, usize) {
+    let mut value: usize = 0;
+    for (i, b) in bytes.iter().enumerate() {
+        if i > 0 {
+            value += 1;
+        }
+        value = (value << 7) | (b & 0x7f) as usize;
+        if b & 0x80 == 0 {
+            return (value, i + 1);
+        }
+    }
+    (value, bytes.len())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    /// The fixtures are written by git 2.39 for `a.txt`, `src/lib.rs` and `src/long/name.txt`, all modified at this time.
+    /// `src/long/name.txt` is skip-worktree in the version 3 and 4 ones, which is why they are not version 2.
+    const MTIME: u64 = 1_700_000_000;
+    const FILES: [(&str, &str); 3] = [("a.txt", "hello\n"), ("src/lib.rs", "fn main() {}\n"), ("src/long/name.txt", "skipped\n")];
+
+    fn work_tree(index: &[u8]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".git/index"), index).unwrap();
+        for (name, content) in FILES {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            set_mtime(&path, MTIME);
+        }
+        dir
+    }
+
+    fn set_mtime(path: &Path, seconds: u64) {
+        File::options().write(true).open(path).unwrap().set_modified(UNIX_EPOCH + Duration::from_secs(seconds)).unwrap();
+    }
+
+    fn dirty(dir: &tempfile::TempDir) -> bool {
+        is_dirty(dir.path(), &dir.path().join(".git"))
+    }
+
+    const INDEXES: [&[u8]; 3] = [include_bytes!("../../tests/fixtures/git/index-v2"), include_bytes!("../../tests/fixtures/git/index-v3"), include_bytes!("../../tests/fixtures/git/index-v4")];
+
+    #[test]
+    fn clean_work_tree() {
+        for index in INDEXES {
+            assert!(!dirty(&work_tree(index)));
+        }
+    }
+
+    #[test]
+    fn changed_size_is_dirty() {
+        for index in INDEXES {
+            let dir = work_tree(index);
+            fs::write(dir.path().join("a.txt"), "hello world\n").unwrap();
+            set_mtime(&dir.path().join("a.txt"), MTIME);
+            assert!(dirty(&dir));
+        }
+    }
+
+    #[test]
+    fn changed_mtime_is_dirty() {
+        for index in INDEXES {
+            let dir = work_tree(index);
+            set_mtime(&dir.path().join("src/lib.rs"), MTIME + 60);
+            assert!(dirty(&dir));
+        }
+    }
+
+    #[test]
+    fn removed_file_is_dirty() {
+        for index in INDEXES {
+            let dir = work_tree(index);
+            fs::remove_file(dir.path().join("a.txt")).unwrap();
+            assert!(dirty(&dir));
+        }
+    }
+
+    #[test]
+    fn skip_worktree_is_not_compared() {
+        let [v2, v3, v4] = INDEXES;
+        for (index, expected) in [(v2, true), (v3, false), (v4, false)] {
+            let dir = work_tree(index);
+            fs::remove_file(dir.path().join("src/long/name.txt")).unwrap();
+            assert_eq!(dirty(&dir), expected);
+        }
+    }
+
+    #[test]
+    fn untracked_file_is_not_dirty() {
+        let dir = work_tree(INDEXES[0]);
+        fs::write(dir.path().join("new.txt"), "new\n").unwrap();
+        assert!(!dirty(&dir));
+    }
+
+    #[test]
+    fn truncated_index_is_not_dirty() {
+        for index in INDEXES {
+            let dir = work_tree(&index[..100]);
+            assert!(!dirty(&dir));
+        }
+    }
+
+    #[test]
+    fn varint() {
+        assert_eq!(read_varint(&[0x05, 0xff]), (5, 1));
+        assert_eq!(read_varint(&[0x7f]), (127, 1));
+        // 0x80 0x00 is 128 in the offset encoding, unlike LEB128.
+        assert_eq!(read_varint(&[0x80, 0x00]), (128, 2));
+        assert_eq!(read_varint(&[0x81, 0x7f]), (383, 2));
+    }
+}
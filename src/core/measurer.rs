@@ -0,0 +1,125 @@
+use std::{
+    collections::VecDeque,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+};
+
+type Task = Box<dyn FnOnce(&AtomicBool) + Send>;
+
+/// A size calculation of the option at `index`. `cancelled` is shared by the jobs submitted before the same `cancel`.
+struct Job {
+    index: usize,
+    cancelled: Arc<AtomicBool>,
+    task: Task,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<Job>,
+    running: usize,
+    workers: usize,
+    shutdown: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Notified when a job is submitted or the pool is dropped.
+    available: Condvar,
+    /// Notified when the queue becomes empty and no job is running.
+    idle: Condvar,
+    /// The indexes of the options shown on the screen, which are calculated first.
+    visible: Arc<Mutex<Vec<usize>>>,
+}
+
+/**
+ The pool calculating the sizes, with at most `threads` threads, so a search finding thousands of targets does not read all of them at once.
+ The jobs of the options shown on the screen are run first, the others in the order they are submitted.
+ */
+pub struct SizePool {
+    threads: usize,
+    shared: Arc<Shared>,
+    cancelled: Mutex<Arc<AtomicBool>>,
+}
+
+impl SizePool {
+    /// `threads` of 0 is the number of CPUs. The threads are started when the jobs come.
+    pub fn new(threads: usize, visible: Arc<Mutex<Vec<usize>>>) -> Self {
+        let threads = if threads == 0 { thread::available_parallelism().map_or(4, |n| n.get()) } else { threads };
+        let shared = Shared { queue: Mutex::new(Queue::default()), available: Condvar::new(), idle: Condvar::new(), visible };
+        SizePool { threads, shared: Arc::new(shared), cancelled: Mutex::new(Arc::new(AtomicBool::new(false))) }
+    }
+    /// The task should stop as soon as the flag it gets is set, see `cancel`.
+    pub fn submit(&self, index: usize, task: impl FnOnce(&AtomicBool) + Send + 'static) {
+        let cancelled = self.cancelled.lock().unwrap().clone();
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.jobs.push_back(Job { index, cancelled, task: Box::new(task) });
+        if queue.workers < self.threads && queue.workers < queue.running + queue.jobs.len() {
+            queue.workers += 1;
+            let shared = self.shared.clone();
+            thread::spawn(move || work(shared));
+        }
+        self.shared.available.notify_one();
+    }
+    /// Drop the jobs waiting and stop the running ones. The jobs submitted after it are not affected.
+    pub fn cancel(&self) {
+        let mut cancelled = self.cancelled.lock().unwrap();
+        cancelled.store(true, Ordering::Relaxed);
+        *cancelled = Arc::new(AtomicBool::new(false));
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.jobs.clear();
+        if queue.running == 0 {
+            self.shared.idle.notify_all();
+        }
+    }
+    /// Block until every job has finished.
+    pub fn join(&self) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        while !queue.jobs.is_empty() || queue.running > 0 {
+            queue = self.shared.idle.wait(queue).unwrap();
+        }
+    }
+}
+
+impl Drop for SizePool {
+    fn drop(&mut self) {
+        self.cancel();
+        self.shared.queue.lock().unwrap().shutdown = true;
+        self.shared.available.notify_all();
+    }
+}
+
+fn work(shared: Arc<Shared>) {
+    loop {
+        let job = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if queue.shutdown {
+                    queue.workers -= 1;
+                    return;
+                }
+                if !queue.jobs.is_empty() {
+                    break;
+                }
+                queue = shared.available.wait(queue).unwrap();
+            }
+            let visible = shared.visible.lock().unwrap();
+            let position = queue.jobs.iter().position(|job| visible.contains(&job.index)).unwrap_or(0);
+            drop(visible);
+            queue.running += 1;
+            queue.jobs.remove(position).unwrap()
+        };
+        if !job.cancelled.load(Ordering::Relaxed) {
+            // A panicking job should not stop the worker, or `join` would wait for it forever.
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| (job.task)(&job.cancelled)));
+        }
+        let mut queue = shared.queue.lock().unwrap();
+        queue.running -= 1;
+        if queue.jobs.is_empty() && queue.running == 0 {
+            shared.idle.notify_all();
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_yaml::{Mapping, Value};
+
+/**
+ The version of the config schema written by this kmall. Bump it and add a step to `MIGRATIONS` when a config of the old schema
+ can not be read as it is, or when a key is added which an older kmall would ignore and then match other dirs than the config means,
+ like `contains_content` or `exclude`. The older kmall refuses the config instead. The keys which only change how the targets are shown
+ or measured, like `size_threads`, do not need it.
+ */
+pub const CONFIG_VERSION: u64 = 6;
+
+/// `MIGRATIONS[i]` upgrades a config document from the version `i + 1` to `i + 2`.
+const MIGRATIONS: [fn(&mut Value); 5] = [migrate_v1, migrate_v2, migrate_v3, migrate_v4, migrate_v5];
+
+/**
+ The configs without `version` are the version 1 if they have a rule of the old schema, with `target` or `excludes_file_dirs`.
+ Without such a rule there is nothing to upgrade, like a layer of only `delete_mode: trash`, so it is the current version.
+ */
+pub fn version_of(doc: &Value) -> u64 {
+    match doc.get("version") {
+        Some(version) => version.as_u64().unwrap_or(1),
+        None if has_old_rules(doc) => 1,
+        None => CONFIG_VERSION,
+    }
+}
+
+fn has_old_rules(doc: &Value) -> bool {
+    let mut rules = doc.get("includes").and_then(Value::as_sequence).into_iter().flatten();
+    rules.any(|rule| rule.get("target").is_some() || rule.get("excludes_file_dirs").is_some())
+}
+
+/**
+ Upgrade the document to `CONFIG_VERSION` in memory and returns the version it was.
+ A config of a newer kmall can not be read, because the meaning of its keys is unknown.
+ */
+pub fn migrate(doc: &mut Value) -> Result<u64, String> {
+    let from = version_of(doc);
+    if from > CONFIG_VERSION {
+        return Err(format!("the config version {} is newer than the version {} supported by this kmall, please upgrade kmall", from, CONFIG_VERSION));
+    }
+    if from == 0 {
+        return Err("the config version should start from 1".to_owned());
+    }
+    // An empty file has nothing to upgrade.
+    if doc.is_null() {
+        return Ok(from);
+    }
+    for step in &MIGRATIONS[(from - 1) as usize..] {
+        step(doc);
+    }
+    // The version is put at the top, where it is written by `init`.
+    if let Value::Mapping(map) = doc {
+        map.remove("version");
+        let mut upgraded = Mapping::new();
+        upgraded.insert(Value::from("version"), Value::from(CONFIG_VERSION));
+        upgraded.extend(std::mem::take(map));
+        *map = upgraded;
+    }
+    Ok(from)
+}
+
+/**
+ Version 1 to 2:
+ - the misspelled `excludes_file_dirs` of a rule, as it was written in the readme, is renamed to `excludes_files_dirs`.
+ - `contains_files_dirs` and `excludes_files_dirs` are written as `[]` if they are missing.
+ */
+fn migrate_v1(doc: &mut Value) {
+    for rule in rules_of(doc) {
+        if let Some(excludes) = rule.remove("excludes_file_dirs") {
+            if !rule.contains_key("excludes_files_dirs") {
+                rule.insert(Value::from("excludes_files_dirs"), excludes);
+            }
+        }
+        for key in ["contains_files_dirs", "excludes_files_dirs"] {
+            if !rule.contains_key(key) {
+                rule.insert(Value::from(key), Value::Sequence(vec![]));
+            }
+        }
+    }
+}
+
+/**
+ Version 2 to 3: `presets` is added. Nothing is changed, the version only keeps an older kmall from ignoring the presets silently.
+ */
+fn migrate_v2(_doc: &mut Value) {}
+
+/// Version 3 to 4: the `target` of a rule becomes a list `targets`.
+fn migrate_v3(doc: &mut Value) {
+    for rule in rules_of(doc) {
+        if let Some(target) = rule.remove("target") {
+            if !rule.contains_key("targets") {
+                let mut upgraded = Mapping::new();
+                upgraded.insert(Value::from("targets"), Value::Sequence(vec![target]));
+                upgraded.extend(std::mem::take(rule));
+                *rule = upgraded;
+            }
+        }
+    }
+}
+
+/**
+ Version 4 to 5: `contains_content` of a rule is added. Nothing is changed, the version keeps an older kmall from ignoring it,
+ which would match more dirs than the rule means.
+ */
+fn migrate_v4(_doc: &mut Value) {}
+
+/**
+ Version 5 to 6: `resolver` of a rule, and `exclude`, `max_depth`, `global_gitignore` and `one_file_system` are added. Nothing is changed,
+ the version keeps an older kmall from ignoring them, which would match the default build dir instead of the redirected one,
+ or the targets in the dirs the config skips.
+ */
+fn migrate_v5(_doc: &mut Value) {}
+
+fn rules_of(doc: &mut Value) -> impl Iterator<Item = &mut Mapping> {
+    doc.get_mut("includes").and_then(Value::as_sequence_mut).into_iter().flatten().filter_map(Value::as_mapping_mut)
+}
+
+/// The config files of an older version, with their versions. The files which can not be parsed are left to the loading to report.
+pub fn outdated_configs(paths: &[PathBuf]) -> Vec<(PathBuf, u64)> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            let doc: Value = serde_yaml::from_str(&fs::read_to_string(path).ok()?).ok()?;
+            let version = version_of(&doc);
+            if doc.is_null() || version >= CONFIG_VERSION {
+                None
+            } else {
+                Some((path.to_owned(), version))
+            }
+        })
+        .collect()
+}
+
+/**
+ Write the upgraded config back to the file. The old one is kept as `config.yaml.v1.bak` (or `config.yaml.v1.bak.2`...) beside it.
+ Returns the path of the backup.
+ */
+pub fn upgrade_config(path: &Path) -> Result<PathBuf, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut doc: Value = serde_yaml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let from = migrate(&mut doc).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let upgraded = serde_yaml::to_string(&doc).map_err(|e| e.to_string())?;
+    let name = format!("{}.v{}.bak", path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default(), from);
+    let mut backup = path.with_file_name(&name);
+    let mut i = 2;
+    while backup.exists() {
+        backup = path.with_file_name(format!("{}.{}", name, i));
+        i += 1;
+    }
+    fs::copy(path, &backup).map_err(|e| format!("can not back up {}: {}", path.display(), e))?;
+    fs::write(path, upgraded).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::checker::ConfigChecker;
+
+    /// A config of the first kmall, with the misspelled key of the old readme.
+    const V1: &str = "includes:
+- target: node_modules
+  contains_files_dirs:
+  - package.json
+  excludes_file_dirs:
+  - .killignore
+  prefix: '[Node]'
+- target: target
+  contains_files_dirs:
+  - Cargo.toml
+  prefix: '[Rust]'
+shallow:
+- node_modules
+";
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn migrate_v1_to_current() {
+        let mut doc = yaml(V1);
+        assert_eq!(migrate(&mut doc), Ok(1));
+        let expected = yaml(&format!(
+            "version: {}
+includes:
+- targets: [node_modules]
+  contains_files_dirs: [package.json]
+  prefix: '[Node]'
+  excludes_files_dirs: [.killignore]
+- targets: [target]
+  contains_files_dirs: [Cargo.toml]
+  prefix: '[Rust]'
+  excludes_files_dirs: []
+shallow: [node_modules]
+",
+            CONFIG_VERSION
+        ));
+        assert_eq!(doc, expected);
+        // The version and `targets` are put first, as `init` writes them.
+        let keys: Vec<&Value> = doc.as_mapping().unwrap().keys().collect();
+        assert_eq!(keys[0], &Value::from("version"));
+        let rule = doc["includes"][0].as_mapping().unwrap();
+        assert_eq!(rule.keys().next(), Some(&Value::from("targets")));
+    }
+
+    #[test]
+    fn migrate_keeps_the_new_key() {
+        let mut doc = yaml("includes:\n- target: a\n  targets: [b]\n  excludes_file_dirs: [x]\n  excludes_files_dirs: [y]\n  prefix: p\n");
+        migrate(&mut doc).unwrap();
+        assert_eq!(doc["includes"][0]["targets"], yaml("[b]"));
+        assert_eq!(doc["includes"][0]["excludes_files_dirs"], yaml("[y]"));
+        assert_eq!(doc["includes"][0].get("target"), None);
+        assert_eq!(doc["includes"][0].get("excludes_file_dirs"), None);
+    }
+
+    #[test]
+    fn migrate_current_is_unchanged() {
+        let text = format!("version: {}\nincludes: []\nshallow: []\n", CONFIG_VERSION);
+        let mut doc = yaml(&text);
+        assert_eq!(migrate(&mut doc), Ok(CONFIG_VERSION));
+        assert_eq!(doc, yaml(&text));
+    }
+
+    #[test]
+    fn migrate_empty_file() {
+        let mut doc = Value::Null;
+        assert_eq!(migrate(&mut doc), Ok(CONFIG_VERSION));
+        assert_eq!(doc, Value::Null);
+    }
+
+    #[test]
+    fn newer_version_is_an_error() {
+        let mut doc = yaml(&format!("version: {}\nincludes: []\n", CONFIG_VERSION + 1));
+        let error = migrate(&mut doc).unwrap_err();
+        assert!(error.contains("newer"), "{}", error);
+        assert!(migrate(&mut yaml("version: 0\n")).is_err());
+    }
+
+    #[test]
+    fn outdated_configs_are_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.yaml");
+        let current = dir.path().join("current.yaml");
+        let empty = dir.path().join("empty.yaml");
+        let layer = dir.path().join("layer.yaml");
+        let rules = dir.path().join("rules.yaml");
+        fs::write(&old, V1).unwrap();
+        fs::write(&current, format!("version: {}\n", CONFIG_VERSION)).unwrap();
+        fs::write(&empty, "").unwrap();
+        // The layers without `version` are only outdated with a rule of the old schema.
+        fs::write(&layer, "delete_mode: trash\n").unwrap();
+        fs::write(&rules, "includes:\n- targets: [dist]\n  contains_files_dirs: [package.json]\n  excludes_files_dirs: []\n  prefix: '[Dist]'\n").unwrap();
+        let paths = [old.clone(), current, empty, layer, rules, dir.path().join("missing.yaml")];
+        assert_eq!(outdated_configs(&paths), vec![(old, 1)]);
+    }
+
+    #[test]
+    fn upgrade_writes_back_and_backs_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(&path, V1).unwrap();
+        let backup = upgrade_config(&path).unwrap();
+        assert_eq!(backup, dir.path().join("config.yaml.v1.bak"));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), V1);
+        assert_eq!(version_of(&yaml(&fs::read_to_string(&path).unwrap())), CONFIG_VERSION);
+        // The upgraded file is loaded as it is, without any error.
+        assert!(ConfigChecker::load(std::slice::from_ref(&path)).is_ok());
+        assert!(outdated_configs(std::slice::from_ref(&path)).is_empty());
+    }
+
+    #[test]
+    fn backup_names_are_not_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(dir.path().join("config.yaml.v1.bak"), "kept").unwrap();
+        for expected in ["config.yaml.v1.bak.2", "config.yaml.v1.bak.3"] {
+            fs::write(&path, V1).unwrap();
+            assert_eq!(upgrade_config(&path).unwrap(), dir.path().join(expected));
+        }
+        assert_eq!(fs::read_to_string(dir.path().join("config.yaml.v1.bak")).unwrap(), "kept");
+    }
+
+    #[test]
+    fn upgrade_does_not_touch_a_newer_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let text = format!("version: {}\n", CONFIG_VERSION + 1);
+        fs::write(&path, &text).unwrap();
+        assert!(upgrade_config(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -1,2 +1,16 @@
 pub mod selector;
-pub mod checker;
\ No newline at end of file
+pub mod checker;
+pub mod filter;
+pub mod remover;
+pub mod view;
+pub mod pattern;
+pub mod staleness;
+pub mod git;
+pub mod migration;
+pub mod presets;
+pub mod content;
+pub mod resolver;
+pub mod size;
+pub mod measurer;
+pub mod progress;
+pub mod traversal;
\ No newline at end of file
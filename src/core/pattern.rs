@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+/**
+ A name of a file or a dir in the rules. In the config it can be:
+ - a string, matching the exact name: `package.json`
+ - a glob: `{ glob: "*.csproj" }`
+ - a regex, which should match the whole name: `{ regex: "build-.+" }`
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(try_from = "PatternDef", into = "PatternDef")]
+pub struct NamePattern {
+    def: PatternDef,
+    matcher: Matcher,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+enum PatternDef {
+    Exact(String),
+    Glob { glob: String },
+    Regex { regex: String },
+}
+
+#[derive(Clone, Debug)]
+enum Matcher {
+    Exact(String),
+    Glob(glob::Pattern),
+    Regex(regex::Regex),
+}
+
+impl NamePattern {
+    pub fn exact(name: &str) -> Self {
+        NamePattern { def: PatternDef::Exact(name.to_owned()), matcher: Matcher::Exact(name.to_owned()) }
+    }
+    pub fn glob(glob: &str) -> Result<Self, String> {
+        PatternDef::Glob { glob: glob.to_owned() }.try_into()
+    }
+    pub fn regex(regex: &str) -> Result<Self, String> {
+        PatternDef::Regex { regex: regex.to_owned() }.try_into()
+    }
+    pub fn matches(&self, name: &str) -> bool {
+        match &self.matcher {
+            Matcher::Exact(exact) => exact == name,
+            Matcher::Glob(pattern) => pattern.matches(name),
+            Matcher::Regex(regex) => regex.is_match(name),
+        }
+    }
+    /// The first name matched.
+    pub fn find<'a>(&self, names: &'a [String]) -> Option<&'a String> {
+        names.iter().find(|name| self.matches(name))
+    }
+}
+
+impl PartialEq for NamePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.def == other.def
+    }
+}
+
+impl TryFrom<PatternDef> for NamePattern {
+    type Error = String;
+
+    fn try_from(def: PatternDef) -> Result<Self, Self::Error> {
+        let matcher = match &def {
+            PatternDef::Exact(name) => Matcher::Exact(name.clone()),
+            PatternDef::Glob { glob } => Matcher::Glob(glob::Pattern::new(glob).map_err(|e| format!("invalid glob \"{}\": {}", glob, e))?),
+            PatternDef::Regex { regex } => Matcher::Regex(regex::Regex::new(&format!("^(?:{})$", regex)).map_err(|e| format!("invalid regex \"{}\": {}", regex, e))?),
+        };
+        Ok(NamePattern { def, matcher })
+    }
+}
+
+impl From<NamePattern> for PatternDef {
+    fn from(pattern: NamePattern) -> Self {
+        pattern.def
+    }
+}
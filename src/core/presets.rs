@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use super::{checker::Checker, pattern::NamePattern};
+
+/// The built-in rules which can be switched on by `presets` in the config, instead of writing each rule.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    Python,
+    Gradle,
+    Maven,
+    Nextjs,
+    Cmake,
+    Elixir,
+    Haskell,
+    Swift,
+    Dart,
+    Unity,
+    Terraform,
+    Zig,
+}
+
+fn rule(targets: &[NamePattern], contains: &[NamePattern], prefix: &str) -> Checker {
+    Checker::new(targets.to_vec(), contains.to_vec(), vec![], prefix)
+}
+
+fn exact(name: &str) -> NamePattern {
+    NamePattern::exact(name)
+}
+
+fn glob(glob: &str) -> NamePattern {
+    NamePattern::glob(glob).unwrap()
+}
+
+fn regex(regex: &str) -> NamePattern {
+    NamePattern::regex(regex).unwrap()
+}
+
+impl Preset {
+    pub fn rules(self) -> Vec<Checker> {
+        match self {
+            Preset::Python => vec![rule(&[exact(".venv"), exact("__pycache__"), exact(".tox")], &[], "[Python]")],
+            Preset::Gradle => vec![
+                rule(&[exact("build")], &[glob("build.gradle*")], "[Gradle]"),
+                rule(&[exact(".gradle")], &[regex(r"(build|settings)\.gradle(\.kts)?")], "[Gradle]"),
+            ],
+            Preset::Maven => vec![rule(&[exact("target")], &[exact("pom.xml")], "[Maven]")],
+            Preset::Nextjs => vec![rule(&[exact(".next")], &[exact("package.json")], "[Next.js]")],
+            Preset::Cmake => vec![rule(&[exact("build")], &[exact("CMakeLists.txt")], "[CMake]")],
+            Preset::Elixir => vec![rule(&[exact("_build"), exact("deps")], &[exact("mix.exs")], "[Elixir]")],
+            Preset::Haskell => vec![rule(&[exact(".stack-work")], &[exact("stack.yaml")], "[Haskell]")],
+            Preset::Swift => vec![rule(&[exact(".build")], &[exact("Package.swift")], "[Swift]")],
+            Preset::Dart => vec![rule(&[exact(".dart_tool")], &[exact("pubspec.yaml")], "[Dart]")],
+            Preset::Unity => vec![rule(&[exact("Library")], &[exact("Assets"), exact("ProjectSettings")], "[Unity]")],
+            Preset::Terraform => vec![rule(&[exact(".terraform")], &[glob("*.tf")], "[Terraform]")],
+            // `.zig-cache` since Zig 0.13.
+            Preset::Zig => vec![rule(&[exact("zig-cache"), exact(".zig-cache")], &[exact("build.zig")], "[Zig]")],
+        }
+    }
+    /**
+     The target names which are never searched into, because there is no project inside.
+     The common names like `build` or `deps` are not in it, because projects can be in a dir with the same name.
+     */
+    pub fn shallow(self) -> &'static [&'static str] {
+        match self {
+            Preset::Python => &[".venv", "__pycache__", ".tox"],
+            Preset::Gradle => &[".gradle"],
+            Preset::Nextjs => &[".next"],
+            Preset::Haskell => &[".stack-work"],
+            Preset::Swift => &[".build"],
+            Preset::Dart => &[".dart_tool"],
+            Preset::Terraform => &[".terraform"],
+            Preset::Zig => &["zig-cache", ".zig-cache"],
+            Preset::Maven | Preset::Cmake | Preset::Elixir | Preset::Unity => &[],
+        }
+    }
+}
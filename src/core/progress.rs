@@ -0,0 +1,58 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/**
+ How the search is going. A dir is counted by `enter` before it is submitted to the pool and by `leave` after it is read,
+ so the search is done exactly when no dir is pending.
+ */
+#[derive(Debug)]
+pub struct Progress {
+    pending: AtomicUsize,
+    scanned: AtomicUsize,
+    started: Instant,
+    /// How long the search took, once it is done.
+    finished: Mutex<Option<Duration>>,
+    done: Condvar,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress { pending: AtomicUsize::new(0), scanned: AtomicUsize::new(0), started: Instant::now(), finished: Mutex::new(None), done: Condvar::new() }
+    }
+}
+
+impl Progress {
+    pub fn enter(&self) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+    }
+    pub fn leave(&self) {
+        self.scanned.fetch_add(1, Ordering::Relaxed);
+        if self.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+            *self.finished.lock().unwrap() = Some(self.started.elapsed());
+            self.done.notify_all();
+        }
+    }
+    /// The number of the dirs read.
+    pub fn scanned(&self) -> usize {
+        self.scanned.load(Ordering::Relaxed)
+    }
+    pub fn is_done(&self) -> bool {
+        self.finished.lock().unwrap().is_some()
+    }
+    /// How long it has been searching, or how long the search took.
+    pub fn elapsed(&self) -> Duration {
+        self.finished.lock().unwrap().unwrap_or_else(|| self.started.elapsed())
+    }
+    /// Block until the search is done. The sizes can still be calculating.
+    pub fn wait(&self) {
+        let mut finished = self.finished.lock().unwrap();
+        while finished.is_none() {
+            finished = self.done.wait(finished).unwrap();
+        }
+    }
+}
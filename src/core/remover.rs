@@ -0,0 +1,245 @@
+use std::{
+    fs, io,
+    path::{self, Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, clap::ValueEnum, Clone, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteMode {
+    /// Remove the dir and all its contents. It can not be recovered.
+    #[default]
+    Permanent,
+    /// Move the dir into the trash, following the freedesktop.org Trash specification.
+    Trash,
+}
+
+pub fn remove_dir(path: &str, mode: &DeleteMode) -> io::Result<()> {
+    match mode {
+        DeleteMode::Permanent => fs::remove_dir_all(path),
+        DeleteMode::Trash => move_to_trash(Path::new(path)),
+    }
+}
+
+/**
+ Remove the target found by searching `root`. A symlinked target is only unlinked in both modes, the dir it leads to is kept.
+ Fails if the target is reached by a symlinked parent out of the root, see `check_target`.
+ */
+pub fn remove_target(root: &Path, path: &Path, mode: &DeleteMode) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return fs::remove_file(path);
+    }
+    check_target(root, path)?;
+    remove_dir(&path.to_string_lossy(), mode)
+}
+
+/**
+ Fails if the target is under the root but its real dir is not, because a dir on the way is a symlink leading out of it.
+ So the data out of the root is never removed by following a symlink. Both the root as it is given and the real root are checked,
+ because a resolver joins the build dir to the real project. The targets out of the root, like a shared `CARGO_TARGET_DIR`, are not checked.
+ */
+pub fn check_target(root: &Path, path: &Path) -> io::Result<()> {
+    let real_root = fs::canonicalize(root)?;
+    let absolute = lexical(path)?;
+    if !absolute.starts_with(&real_root) && !absolute.starts_with(lexical(root)?) {
+        return Ok(());
+    }
+    let real = fs::canonicalize(path)?;
+    if !real.starts_with(&real_root) {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("it leads to {} out of the search path by a symlink", real.display())));
+    }
+    Ok(())
+}
+
+/// The absolute path with `.` and `..` folded without reading the file system, so the symlinks in it are not followed.
+fn lexical(path: &Path) -> io::Result<PathBuf> {
+    let mut folded = PathBuf::new();
+    for component in path::absolute(path)?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                folded.pop();
+            }
+            component => folded.push(component),
+        }
+    }
+    Ok(folded)
+}
+
+/**
+ Move the dir into `$XDG_DATA_HOME/Trash` (`~/.local/share/Trash` by default) and write the `.trashinfo` beside it,
+ so that it can be restored by file managers or `gio trash`/`trash-restore`.
+ If the dir is on another file system than the home trash, `$topdir/.Trash-$uid` of its mount point is used instead, because a dir can not be renamed across devices.
+ */
+#[cfg(unix)]
+pub fn move_to_trash(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let path = fs::canonicalize(path)?;
+    let device = fs::symlink_metadata(&path)?.dev();
+    let home_trash = home_trash_dir()?;
+    fs::create_dir_all(&home_trash)?;
+    let (trash, original) = if fs::metadata(&home_trash)?.dev() == device {
+        (home_trash, path.clone())
+    } else {
+        let top_dir = mount_point(&path, device);
+        let trash = top_dir.join(format!(".Trash-{}", unsafe { libc::getuid() }));
+        let original = path.strip_prefix(&top_dir).map(|p| p.to_path_buf()).unwrap_or_else(|_| path.clone());
+        (trash, original)
+    };
+    let files_dir = trash.join("files");
+    let info_dir = trash.join("info");
+    create_private_dir(&files_dir)?;
+    create_private_dir(&info_dir)?;
+
+    let (name, info_path) = reserve_trash_info(&path, &info_dir, &original)?;
+    if let Err(e) = fs::rename(&path, files_dir.join(name)) {
+        let _ = fs::remove_file(info_path);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn move_to_trash(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "the trash is only supported on unix"))
+}
+
+#[cfg(unix)]
+fn home_trash_dir() -> io::Result<PathBuf> {
+    if let Some(data_home) = std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(data_home).join("Trash"));
+    }
+    match std::env::var_os("HOME") {
+        Some(home) => Ok(PathBuf::from(home).join(".local/share/Trash")),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, "can not find the home dir for the trash")),
+    }
+}
+
+/// The highest ancestor of the path which is still on the same device.
+#[cfg(unix)]
+fn mount_point(path: &Path, device: u64) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut top_dir = path.to_path_buf();
+    while let Some(parent) = top_dir.parent() {
+        match fs::metadata(parent) {
+            Ok(m) if m.dev() == device => top_dir = parent.to_path_buf(),
+            _ => break,
+        }
+    }
+    top_dir
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    if dir.is_dir() {
+        return Ok(());
+    }
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)
+}
+
+/// Create `info/<name>.trashinfo` with a name not used yet. Creating it exclusively is what makes the name ours.
+#[cfg(unix)]
+fn reserve_trash_info(path: &Path, info_dir: &Path, original: &Path) -> io::Result<(String, PathBuf)> {
+    use std::io::Write;
+
+    let base = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| "unnamed".to_owned());
+    let content = format!("[Trash Info]\nPath={}\nDeletionDate={}\n", encode_path(original), deletion_date());
+    for i in 1.. {
+        let name = if i == 1 { base.clone() } else { format!("{}.{}", base, i) };
+        let info_path = info_dir.join(format!("{}.trashinfo", name));
+        match fs::OpenOptions::new().write(true).create_new(true).open(&info_path) {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())?;
+                return Ok((name, info_path));
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+/// Percent-encode the path like an URL, keeping the `/`.
+#[cfg(unix)]
+fn encode_path(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut encoded = String::new();
+    for b in path.as_os_str().as_bytes() {
+        match *b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(*b as char),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+/// The local time as `YYYY-MM-DDThh:mm:ss`.
+#[cfg(unix)]
+fn deletion_date() -> String {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday, tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    /// `scan` is the path searched, `shared` is out of it.
+    fn dirs() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for d in ["scan/p", "shared/src"] {
+            fs::create_dir_all(dir.path().join(d)).unwrap();
+        }
+        fs::write(dir.path().join("shared/src/a.js"), "a").unwrap();
+        dir
+    }
+
+    #[test]
+    fn symlinked_target_is_unlinked() {
+        let dir = dirs();
+        let target = dir.path().join("scan/p/node_modules");
+        for mode in [DeleteMode::Permanent, DeleteMode::Trash] {
+            symlink(dir.path().join("shared"), &target).unwrap();
+            remove_target(&dir.path().join("scan"), &target, &mode).unwrap();
+            assert!(fs::symlink_metadata(&target).is_err());
+            assert!(dir.path().join("shared/src/a.js").exists());
+        }
+    }
+
+    #[test]
+    fn symlinked_parent_out_of_the_root_is_not_removed() {
+        let dir = dirs();
+        symlink(dir.path().join("shared"), dir.path().join("scan/link")).unwrap();
+        let target = dir.path().join("scan/link/src");
+        assert!(remove_target(&dir.path().join("scan"), &target, &DeleteMode::Permanent).is_err());
+        assert!(dir.path().join("shared/src/a.js").exists());
+        // The same through the real root, like the paths joined by a resolver.
+        let real_root = fs::canonicalize(dir.path().join("scan")).unwrap();
+        assert!(check_target(&dir.path().join("scan/./p/.."), &real_root.join("link/src")).is_err());
+    }
+
+    #[test]
+    fn symlinked_parent_in_the_root_is_removed() {
+        let dir = dirs();
+        fs::create_dir_all(dir.path().join("scan/p/dist")).unwrap();
+        symlink(dir.path().join("scan/p"), dir.path().join("scan/link")).unwrap();
+        remove_target(&dir.path().join("scan"), &dir.path().join("scan/link/dist"), &DeleteMode::Permanent).unwrap();
+        assert!(!dir.path().join("scan/p/dist").exists());
+    }
+
+    #[test]
+    fn target_out_of_the_root_is_removed() {
+        let dir = dirs();
+        remove_target(&dir.path().join("scan"), &dir.path().join("scan/../shared/src"), &DeleteMode::Permanent).unwrap();
+        assert!(!dir.path().join("shared/src").exists());
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    env, fs,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// How a rule finds the build dir of a project when it is not at the default place.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolver {
+    /// `CARGO_TARGET_DIR` (or `CARGO_BUILD_TARGET_DIR`), then `build.target-dir` of `.cargo/config.toml` from the project up to the root, then of `$CARGO_HOME/config.toml`.
+    Cargo,
+    /// `compilerOptions.outDir` of `tsconfig.json`.
+    Tsconfig,
+}
+
+impl Resolver {
+    /**
+     The dir the project builds into, if it is set and exists. A dir which is the project or contains it, or contains the path searched (`root`),
+     like `"outDir": "."` or `target-dir = ".."`, is not a build dir, or removing it would remove the sources too.
+     */
+    pub fn resolve(self, project: &Path, root: &Path) -> Option<PathBuf> {
+        let dir = match self {
+            Resolver::Cargo => cargo_target_dir(project),
+            Resolver::Tsconfig => tsconfig_out_dir(project),
+        }?;
+        let real = fs::canonicalize(&dir).ok().filter(|d| d.is_dir())?;
+        if fs::canonicalize(project).ok()?.starts_with(&real) || fs::canonicalize(root).ok()?.starts_with(&real) {
+            return None;
+        }
+        Some(dir)
+    }
+}
+
+fn cargo_target_dir(project: &Path) -> Option<PathBuf> {
+    for name in ["CARGO_TARGET_DIR", "CARGO_BUILD_TARGET_DIR"] {
+        if let Some(dir) = env::var_os(name).filter(|d| !d.is_empty()) {
+            // Relative to the dir cargo runs in, which is supposed to be the one kmall runs in.
+            return Some(join(&env::current_dir().ok()?, Path::new(&dir)));
+        }
+    }
+    let project = fs::canonicalize(project).ok()?;
+    // The closest config has the highest priority.
+    for dir in project.ancestors() {
+        for name in ["config.toml", "config"] {
+            if let Some(target_dir) = read_target_dir(&dir.join(".cargo").join(name)) {
+                return Some(join(dir, Path::new(&target_dir)));
+            }
+        }
+    }
+    let cargo_home = env::var_os("CARGO_HOME").map(PathBuf::from).or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")))?;
+    for name in ["config.toml", "config"] {
+        if let Some(target_dir) = read_target_dir(&cargo_home.join(name)) {
+            return Some(join(cargo_home.parent().unwrap_or(&cargo_home), Path::new(&target_dir)));
+        }
+    }
+    None
+}
+
+fn read_target_dir(config: &Path) -> Option<String> {
+    let config: toml::Value = toml::from_str(&fs::read_to_string(config).ok()?).ok()?;
+    Some(config.get("build")?.get("target-dir")?.as_str()?.to_owned())
+}
+
+fn tsconfig_out_dir(project: &Path) -> Option<PathBuf> {
+    let text = fs::read_to_string(project.join("tsconfig.json")).ok()?;
+    let config: serde_json::Value = serde_json::from_str(&strip_json_comments(&text)).ok()?;
+    let out_dir = config.get("compilerOptions")?.get("outDir")?.as_str()?;
+    Some(join(project, Path::new(out_dir)))
+}
+
+/// Like `Path::join`, but `./lib` is joined as `lib`.
+fn join(base: &Path, path: &Path) -> PathBuf {
+    base.join(path.components().filter(|c| !matches!(c, Component::CurDir)).collect::<PathBuf>())
+}
+
+/// `tsconfig.json` can have comments and trailing commas, which are not JSON. Remove them outside of the strings.
+fn strip_json_comments(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            stripped.push(c);
+            if c == '\\' {
+                stripped.extend(chars.next());
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            _ => {
+                in_string = c == '"';
+                stripped.push(c);
+            }
+        }
+    }
+    // Then remove the commas before `}` or `]`, tracking the strings again.
+    let mut result = String::with_capacity(stripped.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in stripped.char_indices() {
+        if in_string {
+            in_string = escaped || c != '"';
+            escaped = !escaped && c == '\\';
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' && matches!(stripped[i + 1..].trim_start().chars().next(), Some('}') | Some(']')) {
+            continue;
+        }
+        result.push(c);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `scan` is the path searched, with the TypeScript project `scan/ts` and its sibling `scan/other`.
+    fn scan() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for d in ["scan/ts/src", "scan/ts/dist", "scan/other"] {
+            fs::create_dir_all(dir.path().join(d)).unwrap();
+        }
+        dir
+    }
+
+    fn out_dir(dir: &tempfile::TempDir, out_dir: &str) -> Option<PathBuf> {
+        let project = dir.path().join("scan/ts");
+        fs::write(project.join("tsconfig.json"), format!("{{\"compilerOptions\": {{\"outDir\": {}}}}}", serde_json::to_string(out_dir).unwrap())).unwrap();
+        Resolver::Tsconfig.resolve(&project, &dir.path().join("scan"))
+    }
+
+    #[test]
+    fn out_dir_in_the_project() {
+        let dir = scan();
+        assert_eq!(out_dir(&dir, "dist"), Some(dir.path().join("scan/ts/dist")));
+        assert_eq!(out_dir(&dir, "./dist/"), Some(dir.path().join("scan/ts/dist")));
+        assert_eq!(out_dir(&dir, "build"), None);
+    }
+
+    #[test]
+    fn out_dir_out_of_the_project() {
+        let dir = scan();
+        assert_eq!(out_dir(&dir, "../other"), Some(dir.path().join("scan/ts/../other")));
+    }
+
+    #[test]
+    fn out_dir_containing_the_project_or_the_root() {
+        let dir = scan();
+        for out in [".", "./", "..", "../..", "src/.."] {
+            assert_eq!(out_dir(&dir, out), None, "{}", out);
+        }
+        assert_eq!(out_dir(&dir, &dir.path().to_string_lossy()), None);
+        assert_eq!(out_dir(&dir, &dir.path().join("scan").to_string_lossy()), None);
+        assert_eq!(out_dir(&dir, "/"), None);
+    }
+
+    #[test]
+    fn target_dir_containing_the_project() {
+        // The environment overrides the config of the project.
+        if ["CARGO_TARGET_DIR", "CARGO_BUILD_TARGET_DIR"].iter().any(|name| env::var_os(name).is_some_and(|d| !d.is_empty())) {
+            return;
+        }
+        let dir = scan();
+        let project = dir.path().join("scan/rs");
+        fs::create_dir_all(project.join(".cargo")).unwrap();
+        fs::create_dir_all(dir.path().join("scan/shared")).unwrap();
+        for (target_dir, expected) in [("..", None), (".", None), ("./", None), ("../shared", Some(project.join("../shared")))] {
+            fs::write(project.join(".cargo/config.toml"), format!("[build]\ntarget-dir = \"{}\"\n", target_dir)).unwrap();
+            assert_eq!(Resolver::Cargo.resolve(&project, &dir.path().join("scan")), expected, "{}", target_dir);
+        }
+    }
+
+    #[test]
+    fn strip_comments() {
+        let text = "{\n  // the output\n  \"outDir\": \"dist\", /* built\n files */\n  \"rootDir\": \"src\"\n}";
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&strip_json_comments(text)).unwrap(), serde_json::json!({"outDir": "dist", "rootDir": "src"}));
+    }
+
+    #[test]
+    fn strip_comments_keeps_strings() {
+        let text = r#"{"a": "http://x/*y*/", "b": "say \"//hi\"", "c": "back\\"} // end"#;
+        assert_eq!(strip_json_comments(text).trim_end(), r#"{"a": "http://x/*y*/", "b": "say \"//hi\"", "c": "back\\"}"#);
+    }
+
+    #[test]
+    fn strip_trailing_commas() {
+        let text = "{\"a\": [1, 2,], \"b\": \",]\", \"c\": {\"d\": 1,\n},\n}";
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&strip_json_comments(text)).unwrap(), serde_json::json!({"a": [1, 2], "b": ",]", "c": {"d": 1}}));
+    }
+}
@@ -1,106 +1,189 @@
-
 use std::{
-    collections::HashMap,
+    collections::{hash_map::Entry, HashMap, HashSet},
     io::Stdout,
-    sync::{atomic::AtomicBool, Arc, Mutex},
-    thread::{self, available_parallelism},
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc, Mutex, OnceLock},
+    time::SystemTime,
 };
 
 use crate::render::renderer::{self};
-use fs_extra::dir::get_size;
 use rusty_pool::ThreadPool;
 use std::fs;
 
-use super::checker::{get_default_config_path, ConfigChecker};
+use super::checker::{ActiveRepo, ConfigChecker, ConfirmConfig, GitConfig};
+use super::git::{git_status, GitStatus};
+use super::filter::format_age;
+use super::staleness::last_modified;
+use super::remover::{check_target, remove_dir, remove_target, DeleteMode};
+use super::measurer::SizePool;
+use super::progress::Progress;
+use super::traversal::{dir_id, IgnoreChain, Scope, Traversal, IGNORE_FILE};
+use super::size::{dir_size, measure, DirSize};
+use super::view::View;
 
 #[derive(Clone, Debug)]
 pub enum SelectStatus {
     Live,
     Deleting,
     Deleted,
-    System,
+    Failed,
     Searched,
-    Calculating,
+    /// In an active git repository and `git.active` is `protect`. It can not be removed.
+    Protected,
+    /// In an active git repository and `git.active` is `hide`.
+    Hidden,
 }
 #[derive(Clone, Debug)]
 pub struct SelectOptions {
     pub path: String,
+    /// The dir containing the target.
+    pub project: String,
     pub prefix: String,
+    pub target: String,
     pub end: String,
+    /// The bytes freed by removing it, see `DirSize`.
+    pub size: Option<u64>,
+    /// The sum of the file lengths.
+    pub apparent: Option<u64>,
+    /// The last time the project was worked on, without counting the target.
+    /// A target shared by many projects, like a `CARGO_TARGET_DIR`, has the newest time and git activity of all of them.
+    pub modified: Option<SystemTime>,
+    /// `None` if the project is not in a git repository.
+    pub git: Option<GitStatus>,
+    pub marked: bool,
     pub status: SelectStatus,
+    /// The sub-targets found in the target, see `SubTarget`.
+    pub subs: Vec<SubOption>,
 }
 
 impl SelectOptions {
-    pub fn new(path: String, prefix: String, end: String, status: SelectStatus) -> Self {
-        SelectOptions { path, prefix, end, status }
+    pub fn new(path: String, project: String, prefix: String, target: String, end: String, status: SelectStatus) -> Self {
+        SelectOptions { path, project, prefix, target, end, size: None, apparent: None, modified: None, git: None, marked: false, status, subs: vec![] }
     }
 }
+
+/// A sub-target of an option, which can be removed alone. It can be many dirs, like `debug/incremental` and `release/incremental`.
+#[derive(Clone, Debug)]
+pub struct SubOption {
+    pub name: String,
+    pub paths: Vec<PathBuf>,
+    pub end: String,
+    pub size: Option<u64>,
+    pub apparent: Option<u64>,
+    pub status: SelectStatus,
+}
 pub struct Selector {
     value: usize,
     pub options: Arc<Mutex<Vec<SelectOptions>>>,
-    pub ex: HashMap<usize, usize>,
     pub need_refresh: Arc<AtomicBool>,
     pub remove_file_pool: ThreadPool,
     pub search_file_pool: ThreadPool,
+    pub size_pool: Arc<SizePool>,
+    pub delete_mode: DeleteMode,
+    pub confirm: ConfirmConfig,
+    pub view: View,
+    /// The config files loaded, the last one has the highest priority.
+    pub config_files: Vec<PathBuf>,
+    /// The path searched, the targets under it are never removed out of it, see `resolve_target`.
+    root: PathBuf,
 }
 
 impl Selector {
     pub fn new(value: usize) -> Self {
-        Selector { value, options: Arc::new(Mutex::new(Vec::<SelectOptions>::new())), ex: HashMap::new(), need_refresh: Arc::new(AtomicBool::new(false)),  remove_file_pool: ThreadPool::default(), search_file_pool: ThreadPool::default() }
+        let view = View::default();
+        let size_pool = Arc::new(SizePool::new(0, view.visible.clone()));
+        Selector { value, options: Arc::new(Mutex::new(Vec::<SelectOptions>::new())), need_refresh: Arc::new(AtomicBool::new(false)),  remove_file_pool: ThreadPool::default(), search_file_pool: ThreadPool::default(), size_pool, delete_mode: DeleteMode::Permanent, confirm: ConfirmConfig::default(), view, config_files: vec![], root: PathBuf::new() }
     }
-    pub fn init(&mut self, path: std::path::PathBuf) -> bool {
-        if self.search(path) == false {
-            return false;
-        };
-        return true;
+    /// `config` is the file given by `--config`, see `get_config_chain`. Returns why the config can not be loaded.
+    pub fn init(&mut self, path: PathBuf, config: Option<&Path>, scope: &Scope) -> Result<(), String> {
+        self.search(path, config, scope)
     }
 
-    pub fn search(&mut self, path: std::path::PathBuf) -> bool {
-        let cl: ConfigChecker;
-        if let Some(_cl) = ConfigChecker::load_init() {
-            cl = _cl
-        } else {
-            return false;
+    /// `scope` is from the command line, merged over the one of the config.
+    pub fn search(&mut self, path: PathBuf, config: Option<&Path>, scope: &Scope) -> Result<(), String> {
+        let cl = ConfigChecker::load_init(config)?;
+        let scope = Scope { max_depth: scope.max_depth.or(cl.max_depth), exclude: cl.exclude.iter().chain(&scope.exclude).cloned().collect(), global_gitignore: scope.global_gitignore || cl.global_gitignore,
+            follow_symlinks: scope.follow_symlinks || cl.follow_symlinks,
+            one_file_system: scope.one_file_system || cl.one_file_system,
         };
-        let config_loader = Arc::new(cl);
-        let pools = Arc::new(self.search_file_pool.clone());
-        let nr = self.need_refresh.clone();
-        search_files(path.to_str().unwrap(), config_loader, self.options.clone(), pools, nr);
-        return true;
+        let traversal = Traversal::new(&path, &scope)?;
+        self.delete_mode = cl.delete_mode.clone();
+        self.confirm = cl.confirm.clone();
+        self.config_files = cl.files.clone();
+        self.root = path.clone();
+        // The sizes still calculating for the previous search are not needed anymore.
+        self.size_pool.cancel();
+        self.size_pool = Arc::new(SizePool::new(cl.size_threads, self.view.visible.clone()));
+        self.view.progress = Arc::new(Progress::default());
+        let context = SearchContext {
+            config_loader: cl,
+            options: self.options.clone(),
+            pool: self.search_file_pool.clone(),
+            sizes: self.size_pool.clone(),
+            need_refresh: self.need_refresh.clone(),
+            seen: Mutex::new(HashMap::new()),
+            visited: Mutex::new(fs::metadata(&path).ok().and_then(|m| dir_id(&m)).into_iter().collect()),
+            progress: self.view.progress.clone(),
+            traversal,
+        };
+        context.progress.enter();
+        search_files(&path, 0, IgnoreChain::default(), Arc::new(context));
+        Ok(())
     }
-    pub fn add_exit(&mut self) {
-        let mut guard = self.options.lock().unwrap();
-        self.ex.insert(guard.len(), usize::MAX);
-        self.ex.insert(usize::MAX, usize::MAX);
-        guard.push(SelectOptions::new("Exit".to_owned(), "".to_owned(), "".to_owned(), SelectStatus::System));
+    /// Block until every search and size calculation has finished, then return a snapshot of the results.
+    pub fn wait(&self) -> Vec<SelectOptions> {
+        self.view.progress.wait();
+        self.size_pool.join();
+        self.options.lock().unwrap().clone()
     }
-    pub fn value(&self) -> usize {
-        self.value
+    /// Block until every removing has finished, then return a snapshot of the results.
+    pub fn wait_remove(&self) -> Vec<SelectOptions> {
+        self.remove_file_pool.join();
+        self.options.lock().unwrap().clone()
     }
     pub fn render(&mut self, stdout: &mut Stdout) -> usize {
-        let (res, operation) = renderer::selector(stdout, self.options.clone(), &mut self.value, self.need_refresh.clone());
+        let (res, operation) = renderer::selector(stdout, self.options.clone(), &mut self.value, &mut self.view, self.need_refresh.clone(), &self.confirm, self.config_files.last().map(PathBuf::as_path));
         match operation {
-            RendererOperation::SYSTEM => {
-                if self.ex.contains_key(&res) {
-                    self.remove_file_pool.clone().shutdown();
-                    self.search_file_pool.clone().shutdown();
-                    return *self.ex.get(&res).unwrap();
-                }
+            RendererOperation::System if res == usize::MAX => {
+                self.remove_file_pool.clone().shutdown();
+                self.search_file_pool.clone().shutdown();
+                self.size_pool.cancel();
+                return usize::MAX;
             }
-            RendererOperation::REMOVE => {
+            RendererOperation::Remove => {
                 self.remove_file(res);
             }
+            RendererOperation::RemoveMarked => {
+                self.remove_marked();
+            }
+            RendererOperation::RemoveSub(sub) => {
+                self.remove_sub(res, sub);
+            }
             _ => {}
         }
-        return res;
+        res
+    }
+    /// Remove all the marked dirs which are not calculating. The ones still calculating keep their marks.
+    pub fn remove_marked(&mut self) {
+        let indexes: Vec<usize> = {
+            let mut guard = self.options.lock().unwrap();
+            let indexes: Vec<usize> = (0..guard.len()).filter(|i| guard[*i].marked && matches!(guard[*i].status, SelectStatus::Live)).collect();
+            for i in &indexes {
+                guard[*i].marked = false;
+            }
+            indexes
+        };
+        for i in indexes {
+            self.remove_file(i);
+        }
     }
-    fn remove_file(&mut self, res: usize) {
+    pub fn remove_file(&mut self, res: usize) {
         let options_status: SelectStatus;
         let options_len: usize;
         {
             let guard = self.options.lock().unwrap();
             options_len = guard.len();
-            if options_len < res {
+            if options_len <= res {
                 return;
             }
             options_status = guard[res].status.clone();
@@ -116,89 +199,311 @@ impl Selector {
             };
             let _options = self.options.clone();
             let _need_refresh = self.need_refresh.clone();
+            let mode = self.delete_mode.clone();
+            let root = self.root.clone();
             self.remove_file_pool.evaluate(move || {
-                if let Err(e) = fs::remove_dir_all(path) {
+                let result = remove_target(&root, Path::new(&path), &mode);
+                if let Err(e) = result {
                     let mut guard = _options.lock().unwrap();
-                    guard[res].status = SelectStatus::Deleted;
-                    guard[res].end = format!("[Err{}]", e.to_string());
+                    guard[res].status = SelectStatus::Failed;
+                    guard[res].end = format!("[Err{}]", e);
                     _need_refresh.swap(true, std::sync::atomic::Ordering::Relaxed);
                 } else {
                     let mut guard = _options.lock().unwrap();
                     guard[res].status = SelectStatus::Deleted;
-                    guard[res].end = "[Removed]".to_string();
+                    guard[res].end = match mode {
+                        DeleteMode::Permanent => "[Removed]".to_string(),
+                        DeleteMode::Trash => "[Trashed]".to_string(),
+                    };
                     _need_refresh.swap(true, std::sync::atomic::Ordering::Relaxed);
                 }
             });
         }
     }
+    /// Remove a sub-target of the dir instead of the whole dir. The sizes are calculated again after it, because the sub-targets can overlap.
+    pub fn remove_sub(&mut self, res: usize, sub: usize) {
+        let (path, paths) = {
+            let mut guard = self.options.lock().unwrap();
+            let Some(option) = guard.get_mut(res) else {
+                return;
+            };
+            if !matches!(option.status, SelectStatus::Live) || !option.subs.get(sub).is_some_and(|s| matches!(s.status, SelectStatus::Live)) {
+                return;
+            }
+            option.status = SelectStatus::Deleting;
+            option.end = "[Removing ".to_owned() + &option.subs[sub].name + "]";
+            option.subs[sub].status = SelectStatus::Deleting;
+            option.subs[sub].end = "[Removing]".to_owned();
+            self.need_refresh.swap(true, std::sync::atomic::Ordering::Relaxed);
+            (option.path.clone(), option.subs[sub].paths.clone())
+        };
+        let _options = self.options.clone();
+        let _need_refresh = self.need_refresh.clone();
+        let mode = self.delete_mode.clone();
+        let root = self.root.clone();
+        self.remove_file_pool.evaluate(move || {
+            let result = check_target(&root, Path::new(&path)).and_then(|_| paths.iter().try_for_each(|p| remove_dir(&p.to_string_lossy(), &mode)));
+            let never = AtomicBool::new(false);
+            let size = dir_size(Path::new(&path), &never).ok();
+            let sub_paths: Vec<Vec<PathBuf>> = _options.lock().unwrap()[res].subs.iter().map(|s| s.paths.clone()).collect();
+            let sizes: Vec<DirSize> = sub_paths.iter().map(|p| measure(p, &never)).collect();
+            let mut guard = _options.lock().unwrap();
+            let option = &mut guard[res];
+            for (s, size) in option.subs.iter_mut().zip(sizes) {
+                if let SelectStatus::Live = s.status {
+                    s.size = Some(size.reclaimable);
+                    s.apparent = Some(size.apparent);
+                    s.end = format_size(size);
+                }
+            }
+            option.subs[sub].end = match (&result, &mode) {
+                (Err(e), _) => format!("[Err{}]", e),
+                (Ok(_), DeleteMode::Permanent) => "[Removed]".to_string(),
+                (Ok(_), DeleteMode::Trash) => "[Trashed]".to_string(),
+            };
+            option.subs[sub].status = if result.is_ok() { SelectStatus::Deleted } else { SelectStatus::Failed };
+            if let Some(size) = size {
+                option.size = Some(size.reclaimable);
+                option.apparent = Some(size.apparent);
+            }
+            option.status = SelectStatus::Live;
+            option.end = format_end(size, option.modified, option.git.as_ref());
+            _need_refresh.swap(true, std::sync::atomic::Ordering::Relaxed);
+        });
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum RendererOperation {
-    SYSTEM,
-    REMOVE,
-    ANOTHER,
-    NONE
+    System,
+    Remove,
+    RemoveMarked,
+    /// Remove the sub-target at the index of the returned option.
+    RemoveSub(usize),
+    None
+}
+
+/// What the searching threads share.
+struct SearchContext {
+    config_loader: ConfigChecker,
+    options: Arc<Mutex<Vec<SelectOptions>>>,
+    pool: ThreadPool,
+    sizes: Arc<SizePool>,
+    need_refresh: Arc<AtomicBool>,
+    /// The canonical paths of the targets found with their indexes, because a redirected build dir can be shared by many projects.
+    seen: Mutex<HashMap<PathBuf, usize>>,
+    /// The (device, inode) of the dirs searched, so a symlink loop or a bind mount does not search them again.
+    visited: Mutex<HashSet<(u64, u64)>>,
+    progress: Arc<Progress>,
+    traversal: Traversal,
+}
+
+/// Counts the dir as left when its search ends, even by a panic, or `Progress::wait` would block forever.
+struct Leave<'a>(&'a SearchContext);
+
+impl Drop for Leave<'_> {
+    fn drop(&mut self) {
+        self.0.progress.leave();
+        // Refresh the status line even if nothing is found.
+        self.0.need_refresh.swap(true, std::sync::atomic::Ordering::Relaxed);
+    }
 }
 
-fn search_files(path: &str, config_loader: Arc<ConfigChecker>, options: Arc<Mutex<Vec<SelectOptions>>>, pools: Arc<ThreadPool>, need_refresh: Arc<AtomicBool>) {
-    // let mut input_template_all: Vec<(String, String)> = Vec::new();
+/**
+ The dir should be counted by `Progress::enter` before, it is counted as left after it is read. The root is at the depth 0.
+ `ignores` has the ignore files of the parents, the one of the dir is added before searching into its subdirs.
+ */
+fn search_files(path: &Path, depth: usize, ignores: IgnoreChain, context: Arc<SearchContext>) {
+    let _leave = Leave(&context);
     if let Ok(entries) = fs::read_dir(path) {
         let mut dirs: Vec<String> = vec![];
         let mut files: Vec<String> = vec![];
-        let mut dirs_path: Vec<String> = vec![];
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let file_path = entry.path();
-                if file_path.is_file() {
-                    if let Some(file_name) = file_path.file_name() {
-                        if let Some(name) = file_name.to_str() {
-                            files.push(name.to_owned());
-                        }
+        let mut subdirs: Vec<(PathBuf, fs::Metadata)> = vec![];
+        for entry in entries.flatten() {
+            let file_path = entry.path();
+            let is_symlink = entry.file_type().is_ok_and(|t| t.is_symlink());
+            // Follows the symlinks, a symlinked file is read like the file.
+            let Ok(metadata) = fs::metadata(&file_path) else {
+                continue;
+            };
+            if metadata.is_file() {
+                if let Some(file_name) = file_path.file_name() {
+                    if let Some(name) = file_name.to_str() {
+                        files.push(name.to_owned());
                     }
-                } else if file_path.is_dir() {
-                    if let Some(file_name) = file_path.file_name() {
-                        if let Some(name) = file_name.to_str() {
-                            dirs.push(name.to_owned());
-                            dirs_path.push(file_path.to_str().unwrap().to_owned());
-                            if config_loader.is_shallow(&name.to_owned()) == true {
-                                continue;
-                            }
+                }
+            } else if metadata.is_dir() {
+                if !context.traversal.can_cross(is_symlink, &metadata) {
+                    continue;
+                }
+                if let Some(file_name) = file_path.file_name() {
+                    if let Some(name) = file_name.to_str() {
+                        dirs.push(name.to_owned());
+                        if context.config_loader.is_shallow(&name.to_owned()) {
+                            continue;
                         }
                     }
-                    let _pools = pools.clone();
-                    let _options = options.clone();
-                    let _config_loader = config_loader.clone();
-                    let _nr = need_refresh.clone();
-                    pools.evaluate(move || search_files(file_path.clone().to_str().unwrap(), _config_loader, _options, _pools, _nr));
-                    // let sub_directory = search_files(file_path.to_str().unwrap(), config_loader);
-                    // input_template_all.extend(sub_directory);
                 }
+                if !context.traversal.can_enter(depth + 1) || context.traversal.is_excluded(&file_path) {
+                    continue;
+                }
+                subdirs.push((file_path, metadata));
             }
         }
-        if let Some((prefix, target)) = config_loader.check(&dirs, &files) {
-            let mut index = dirs.iter().position(|r| r == &target.to_owned()).unwrap();
-            {
-                let mut guard = options.lock().unwrap();
-                let r = SelectOptions::new(dirs_path[index].clone(), prefix, "[wait]".to_owned(), SelectStatus::Searched);
-                index = guard.len();
-                guard.push(r);
+        let ignores = ignores.enter(path, files.iter().any(|f| f == IGNORE_FILE));
+        for (subdir, metadata) in subdirs {
+            if context.traversal.is_ignored(&ignores, &subdir) {
+                continue;
             }
-            let _path = path.to_owned().clone();
-            let _opt = options.clone();
-            let _nr = need_refresh.clone();
-            thread::spawn(move || {
-                if let Ok(size) = get_size(&_path) {
-                    let mut guard = _opt.lock().unwrap();
-                    let _size= "[".to_owned() + &(((size as f64 / 1024_f64 / 1024_f64) * 100_f64).round() / 100_f64).to_string() + "Mb]";
-                    guard[index].end = _size.clone();
-                    // msgbox::create("GOT",&_size,IconType::Info).unwrap();
-                    guard[index].status = SelectStatus::Live;
-                    // println!("{},{}",index,_size);
-                    _nr.swap(true, std::sync::atomic::Ordering::Relaxed);
+            if dir_id(&metadata).is_some_and(|id| !context.visited.lock().unwrap().insert(id)) {
+                continue;
+            }
+            let _context = context.clone();
+            let _ignores = ignores.clone();
+            context.progress.enter();
+            context.pool.evaluate(move || search_files(&subdir, depth + 1, _ignores, _context));
+        }
+        let found = context.config_loader.check(context.traversal.root(), path, &dirs, &files);
+        // The targets of the project share its modified time, which is found once without any of them.
+        let targets: Arc<Vec<PathBuf>> = Arc::new(found.iter().map(|(_, target_path)| target_path.clone()).collect());
+        let modified: Arc<OnceLock<Option<SystemTime>>> = Arc::new(OnceLock::new());
+        for (checker, target_path) in found {
+            if context.traversal.is_excluded(&target_path) || context.traversal.is_ignored_target(&ignores, &target_path) {
+                continue;
+            }
+            let (index, shared) = match context.seen.lock().unwrap().entry(fs::canonicalize(&target_path).unwrap_or_else(|_| target_path.clone())) {
+                Entry::Occupied(entry) => (*entry.get(), true),
+                Entry::Vacant(entry) => {
+                    let target = target_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                    let mut guard = context.options.lock().unwrap();
+                    guard.push(SelectOptions::new(target_path.to_string_lossy().into_owned(), path.to_string_lossy().into_owned(), checker.prefix.clone(), target, "[wait]".to_owned(), SelectStatus::Searched));
+                    (*entry.insert(guard.len() - 1), false)
+                }
+            };
+            let _context = context.clone();
+            let _project = path.to_path_buf();
+            let _sub_targets = checker.sub_targets.clone();
+            let _targets = targets.clone();
+            let _modified = modified.clone();
+            let _ignores = ignores.clone();
+            if shared {
+                // Found from another project, its activity counts too.
+                context.sizes.submit(index, move |cancelled| {
+                    let config_loader = &_context.config_loader;
+                    let modified = *_modified.get_or_init(|| last_modified(&_project, &_targets, &config_loader.staleness, &_context.traversal, &_ignores, cancelled));
+                    let git = git_status(&_project);
+                    let mut guard = _context.options.lock().unwrap();
+                    merge_activity(&mut guard[index], modified, git);
+                    show_activity(&mut guard[index], &config_loader.git);
+                    _context.need_refresh.swap(true, std::sync::atomic::Ordering::Relaxed);
+                });
+                continue;
+            }
+            context.sizes.submit(index, move |cancelled| {
+                // A symlinked target is only unlinked, so nothing under it is counted or can be removed alone.
+                let is_symlink = fs::symlink_metadata(&target_path).is_ok_and(|m| m.file_type().is_symlink());
+                if let Ok(size) = dir_size(&target_path, cancelled) {
+                    let config_loader = &_context.config_loader;
+                    let modified = *_modified.get_or_init(|| last_modified(&_project, &_targets, &config_loader.staleness, &_context.traversal, &_ignores, cancelled));
+                    let git = git_status(&_project);
+                    let subs: Vec<SubOption> = _sub_targets
+                        .iter()
+                        .filter(|_| !is_symlink)
+                        .map(|s| (s, s.find(&target_path)))
+                        .filter(|(_, paths)| !paths.is_empty())
+                        .map(|(s, paths)| {
+                            let size = measure(&paths, cancelled);
+                            SubOption { name: s.name.clone(), paths, end: format_size(size), size: Some(size.reclaimable), apparent: Some(size.apparent), status: SelectStatus::Live }
+                        })
+                        .collect();
+                    let mut guard = _context.options.lock().unwrap();
+                    guard[index].subs = subs;
+                    guard[index].size = Some(size.reclaimable);
+                    guard[index].apparent = Some(size.apparent);
+                    merge_activity(&mut guard[index], modified, git);
+                    show_activity(&mut guard[index], &config_loader.git);
+                    _context.need_refresh.swap(true, std::sync::atomic::Ordering::Relaxed);
                 }
             });
-            // input_template_all.push((dirs_path[index].clone(), prefix))
         }
     }
 }
+
+/// Count the activity of a project in the option, the newest of the projects sharing the target wins.
+fn merge_activity(option: &mut SelectOptions, modified: Option<SystemTime>, git: Option<GitStatus>) {
+    option.modified = option.modified.max(modified);
+    option.git = match (option.git.take(), git) {
+        (Some(a), Some(b)) => Some(GitStatus { last_commit: a.last_commit.max(b.last_commit), dirty: a.dirty || b.dirty }),
+        (a, b) => a.or(b),
+    };
+}
+
+/// Show the activity of the option in its row, and protect or hide it in an active repository. It waits until the size is known.
+fn show_activity(option: &mut SelectOptions, config: &GitConfig) {
+    let Some(reclaimable) = option.size else {
+        return;
+    };
+    if !matches!(option.status, SelectStatus::Searched | SelectStatus::Live | SelectStatus::Protected | SelectStatus::Hidden) {
+        return;
+    }
+    let size = DirSize { reclaimable, apparent: option.apparent.unwrap_or(reclaimable) };
+    option.end = format_end(Some(size), option.modified, option.git.as_ref());
+    let active = option.git.as_ref().is_some_and(|g| g.is_active(config.active_within));
+    option.status = match config.active {
+        ActiveRepo::Protect if active => SelectStatus::Protected,
+        ActiveRepo::Hide if active => SelectStatus::Hidden,
+        _ => SelectStatus::Live,
+    };
+}
+
+fn format_mb(size: u64) -> String {
+    (((size as f64 / 1024_f64 / 1024_f64) * 100_f64).round() / 100_f64).to_string() + "Mb"
+}
+
+/// Like `[3.5Mb/12.3Mb]`, the reclaimable size then the apparent size.
+fn format_size(size: DirSize) -> String {
+    format!("[{}/{}]", format_mb(size.reclaimable), format_mb(size.apparent))
+}
+
+/// Like `[git 3 days ago][5 days ago][3.5Mb/12.3Mb]`, shown at the end of the row.
+fn format_end(size: Option<DirSize>, modified: Option<SystemTime>, git: Option<&GitStatus>) -> String {
+    let _age = modified.map_or("[-]".to_owned(), |m| "[".to_owned() + &format_age(m) + "]");
+    let _git = git.map_or("".to_owned(), format_git);
+    _git + &_age + &size.map_or("[-]".to_owned(), format_size)
+}
+
+/// Like `[git* 3 days ago]`, the `*` means there are uncommitted changes.
+fn format_git(git: &GitStatus) -> String {
+    let dirty = if git.dirty { "*" } else { "" };
+    match git.last_commit {
+        Some(last_commit) => format!("[git{} {}]", dirty, format_age(last_commit)),
+        None => format!("[git{}]", dirty),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn shared_target_has_the_newest_activity() {
+        let old = SystemTime::now() - Duration::from_secs(60 * 60 * 24 * 365);
+        let new = SystemTime::now();
+        let mut option = SelectOptions::new("/ws/shared".to_owned(), "/ws/a".to_owned(), "[Rust]".to_owned(), "shared".to_owned(), "[wait]".to_owned(), SelectStatus::Searched);
+        let config = GitConfig { active: ActiveRepo::Protect, ..GitConfig::default() };
+        merge_activity(&mut option, Some(old), Some(GitStatus { last_commit: Some(old), dirty: false }));
+        show_activity(&mut option, &config);
+        // The row waits for the size.
+        assert_eq!(option.end, "[wait]");
+        option.size = Some(0);
+        show_activity(&mut option, &config);
+        assert!(matches!(option.status, SelectStatus::Live));
+        merge_activity(&mut option, Some(new), None);
+        merge_activity(&mut option, None, Some(GitStatus { last_commit: None, dirty: true }));
+        show_activity(&mut option, &config);
+        assert_eq!(option.modified, Some(new));
+        assert_eq!(option.git.as_ref().map(|g| (g.last_commit, g.dirty)), Some((Some(old), true)));
+        assert!(matches!(option.status, SelectStatus::Protected));
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/**
+ The size of dirs in bytes.
+ - `apparent` is the sum of the file lengths, which is what most tools show.
+ - `reclaimable` is the space allocated on the disk which is freed by removing them. A file hardlinked from out of the dirs,
+   like the ones of pnpm linked from its store, is not counted, because its data is still used after removing. A sparse file
+   counts only the blocks it has.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DirSize {
+    pub apparent: u64,
+    pub reclaimable: u64,
+}
+
+/**
+ The size of the dir. Fails if the dir itself can not be read, the entries which can not be read are skipped.
+ Fails with `Interrupted` if `cancelled` is set before it finishes.
+ */
+pub fn dir_size(path: &Path, cancelled: &AtomicBool) -> io::Result<DirSize> {
+    fs::read_dir(path)?;
+    let size = measure(&[path.to_path_buf()], cancelled);
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(io::ErrorKind::Interrupted.into());
+    }
+    Ok(size)
+}
+
+/**
+ The size of the dirs together, the missing ones are skipped. A file hardlinked between them is counted once,
+ like `debug/deps/app-1234` and `debug/app` of a Rust target. Symlinks are never followed.
+ */
+pub fn measure(paths: &[PathBuf], cancelled: &AtomicBool) -> DirSize {
+    let mut size = DirSize::default();
+    // The hardlinked files, by (device, inode): the links found in the dirs, the link count and the allocated bytes.
+    let mut linked: HashMap<(u64, u64), (u64, u64, u64)> = HashMap::new();
+    let mut stack: Vec<PathBuf> = paths.to_vec();
+    while let Some(path) = stack.pop() {
+        if cancelled.load(Ordering::Relaxed) {
+            break;
+        }
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            size.reclaimable += allocated(&metadata);
+            if let Ok(entries) = fs::read_dir(&path) {
+                stack.extend(entries.flatten().map(|e| e.path()));
+            }
+            continue;
+        }
+        if metadata.is_file() {
+            size.apparent += metadata.len();
+        }
+        match inode(&metadata) {
+            Some((key, links)) if links > 1 => linked.entry(key).or_insert((0, links, allocated(&metadata))).0 += 1,
+            _ => size.reclaimable += allocated(&metadata),
+        }
+    }
+    // The data is freed only if every link of it is removed.
+    size.reclaimable += linked.values().filter(|(found, links, _)| found >= links).map(|(_, _, bytes)| bytes).sum::<u64>();
+    size
+}
+
+#[cfg(unix)]
+fn allocated(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // `st_blocks` is always in 512-byte units.
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// The (device, inode) and the link count of the file.
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> Option<((u64, u64), u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some(((metadata.dev(), metadata.ino()), metadata.nlink()))
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> Option<((u64, u64), u64)> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn size_of(path: &Path) -> DirSize {
+        measure(&[path.to_path_buf()], &AtomicBool::new(false))
+    }
+
+    /// The allocated bytes of the dir alone, with no file in it.
+    fn dir_bytes(path: &Path) -> u64 {
+        allocated(&fs::symlink_metadata(path).unwrap())
+    }
+
+    /// Write a file with its data allocated, and return the allocated bytes.
+    fn data(path: &Path) -> u64 {
+        fs::write(path, vec![1u8; 8192]).unwrap();
+        let bytes = allocated(&fs::metadata(path).unwrap());
+        assert!(bytes > 0);
+        bytes
+    }
+
+    #[test]
+    fn file_linked_from_out_of_the_dir_is_not_counted() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("target");
+        fs::create_dir(&dir).unwrap();
+        data(&root.path().join("store"));
+        fs::hard_link(root.path().join("store"), dir.join("linked")).unwrap();
+        let size = size_of(&dir);
+        assert_eq!(size.reclaimable, dir_bytes(&dir));
+        assert_eq!(size.apparent, 8192);
+    }
+
+    #[test]
+    fn links_in_the_dir_are_counted_once() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("target");
+        fs::create_dir_all(dir.join("deps")).unwrap();
+        let bytes = data(&dir.join("deps/app-1234"));
+        fs::hard_link(dir.join("deps/app-1234"), dir.join("app")).unwrap();
+        assert_eq!(size_of(&dir).reclaimable, dir_bytes(&dir) + dir_bytes(&dir.join("deps")) + bytes);
+        // Only one of the links is in `deps`, so removing it alone frees nothing of the file.
+        assert_eq!(size_of(&dir.join("deps")).reclaimable, dir_bytes(&dir.join("deps")));
+    }
+
+    #[test]
+    fn symlink_is_not_followed() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("target");
+        let outside = root.path().join("outside");
+        fs::create_dir(&dir).unwrap();
+        fs::create_dir(&outside).unwrap();
+        data(&outside.join("big"));
+        std::os::unix::fs::symlink(&outside, dir.join("dir_link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("big"), dir.join("file_link")).unwrap();
+        let size = size_of(&dir);
+        let links = allocated(&fs::symlink_metadata(dir.join("dir_link")).unwrap()) + allocated(&fs::symlink_metadata(dir.join("file_link")).unwrap());
+        assert_eq!(size.reclaimable, dir_bytes(&dir) + links);
+        assert_eq!(size.apparent, 0);
+    }
+}
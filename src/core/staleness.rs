@@ -0,0 +1,62 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::SystemTime,
+};
+
+use super::checker::StalenessConfig;
+use super::traversal::{IgnoreChain, Traversal, IGNORE_FILE};
+
+/**
+ The last time the project was worked on: the newest modified time of the files in the project dir, recursively.
+ The targets are skipped, and so are the files and dirs matched by `ignore` of the config, either by the name or by the path relative to the project.
+ The walk stops at `max_depth` levels and `max_entries` entries, and skips the dirs the search skips: the excluded or ignored ones,
+ and the ones on another file system with `one_file_system`. `ignores` is the ignore chain of the project dir. Symlinks are not followed.
+ */
+pub fn last_modified(project: &Path, targets: &[PathBuf], config: &StalenessConfig, traversal: &Traversal, ignores: &IgnoreChain, cancelled: &AtomicBool) -> Option<SystemTime> {
+    let patterns = config.ignore_patterns();
+    let mut newest: Option<SystemTime> = None;
+    let mut entries_read = 0;
+    let mut stack = vec![(project.to_path_buf(), 0, ignores.clone())];
+    while let Some((dir, depth, ignores)) = stack.pop() {
+        if cancelled.load(Ordering::Relaxed) {
+            break;
+        }
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        let ignores = if depth == 0 { ignores } else { ignores.enter(&dir, dir.join(IGNORE_FILE).is_file()) };
+        for entry in entries.flatten() {
+            entries_read += 1;
+            if entries_read > config.max_entries {
+                return newest;
+            }
+            let path = entry.path();
+            if targets.contains(&path) || is_ignored(&path, project, &patterns) {
+                continue;
+            }
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_dir() {
+                if depth < config.max_depth && traversal.can_cross(false, &metadata) && !traversal.is_excluded(&path) && !traversal.is_ignored(&ignores, &path) {
+                    stack.push((path, depth + 1, ignores.clone()));
+                }
+            } else if let Ok(modified) = metadata.modified() {
+                if newest.is_none_or(|n| modified > n) {
+                    newest = Some(modified);
+                }
+            }
+        }
+    }
+    newest
+}
+
+fn is_ignored(path: &Path, project: &Path, ignore: &[glob::Pattern]) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let relative = path.strip_prefix(project).unwrap_or(path);
+    ignore.iter().any(|pattern| pattern.matches(&name) || pattern.matches_path(relative))
+}
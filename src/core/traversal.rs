@@ -0,0 +1,259 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use glob::{MatchOptions, Pattern};
+use ignore::{gitignore::Gitignore, Match};
+
+/// The ignore file read in every dir searched, with the syntax of `.gitignore`.
+pub const IGNORE_FILE: &str = ".kmallignore";
+
+/// How far the search goes, from the config and the command line.
+#[derive(Clone, Debug, Default)]
+pub struct Scope {
+    /// The levels of the dirs under the path which are searched. 0 searches only the path itself.
+    pub max_depth: Option<usize>,
+    /// Globs of the paths which are never searched into, see `Traversal`.
+    pub exclude: Vec<String>,
+    /// Skip the dirs ignored by the global gitignore of git, see `Traversal::is_ignored`.
+    pub global_gitignore: bool,
+    /// Search into the symlinked dirs too, each dir is still searched once, see `dir_id`.
+    pub follow_symlinks: bool,
+    /// Do not search into the dirs on another file system than the path, like mounted disks.
+    pub one_file_system: bool,
+}
+
+/**
+ Decides which dirs the search goes into. An exclude glob starting with `/` or `~/` matches the absolute path,
+ others match the path relative to the search root, like `vendor/*` or `**/fixtures`. `*` does not match `/`, `**` does.
+ */
+#[derive(Debug)]
+pub struct Traversal {
+    root: PathBuf,
+    /// The root as an absolute path, to match the absolute globs.
+    absolute_root: PathBuf,
+    max_depth: Option<usize>,
+    relative: Vec<Pattern>,
+    absolute: Vec<Pattern>,
+    global_gitignore: Option<Gitignore>,
+    follow_symlinks: bool,
+    /// The device of the root with `one_file_system`.
+    device: Option<u64>,
+}
+
+/// Check the glob of `--exclude`, so a wrong one is reported as an argument instead of the config.
+pub fn parse_exclude(s: &str) -> Result<String, String> {
+    Pattern::new(s.trim_end_matches('/')).map_err(|e| e.to_string())?;
+    Ok(s.to_owned())
+}
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions { case_sensitive: true, require_literal_separator: true, require_literal_leading_dot: false };
+
+impl Traversal {
+    pub fn new(root: &Path, scope: &Scope) -> Result<Self, String> {
+        let mut relative = vec![];
+        let mut absolute = vec![];
+        for glob in &scope.exclude {
+            let trimmed = glob.trim_end_matches('/');
+            let (patterns, text) = if let Some(rest) = trimmed.strip_prefix("~/") {
+                let home = env::var_os("HOME").ok_or_else(|| format!("can not expand \"{}\", HOME is not set", glob))?;
+                (&mut absolute, Pattern::escape(&PathBuf::from(home).to_string_lossy()) + "/" + rest)
+            } else if trimmed.starts_with('/') {
+                (&mut absolute, trimmed.to_owned())
+            } else {
+                (&mut relative, trimmed.to_owned())
+            };
+            patterns.push(Pattern::new(&text).map_err(|e| format!("invalid exclude glob \"{}\": {}", glob, e))?);
+        }
+        let absolute_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        // The invalid lines of the global gitignore are skipped, like git does.
+        let global_gitignore = if scope.global_gitignore { Some(Gitignore::global().0) } else { None };
+        let device = if scope.one_file_system { fs::metadata(root).ok().and_then(|m| dir_id(&m)).map(|(device, _)| device) } else { None };
+        Ok(Traversal { root: root.to_path_buf(), absolute_root, max_depth: scope.max_depth, relative, absolute, global_gitignore, follow_symlinks: scope.follow_symlinks, device })
+    }
+    /**
+     Whether the dir can be searched into, by the metadata following the symlinks. A symlinked dir is searched only with `follow_symlinks`,
+     and only on unix where a loop can be found by `dir_id`.
+     */
+    pub fn can_cross(&self, is_symlink: bool, metadata: &fs::Metadata) -> bool {
+        let id = dir_id(metadata);
+        if is_symlink && !(self.follow_symlinks && id.is_some()) {
+            return false;
+        }
+        self.device.is_none_or(|device| id.is_some_and(|(d, _)| d == device))
+    }
+    /// The path searched.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    /// Whether the dir at the depth (the root is 0) can be searched into.
+    pub fn can_enter(&self, depth: usize) -> bool {
+        self.max_depth.is_none_or(|max_depth| depth <= max_depth)
+    }
+    /// Whether the path is excluded, either a dir under the root or a target found by a resolver.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root).ok();
+        if relative.is_some_and(|relative| self.relative.iter().any(|p| p.matches_path_with(relative, MATCH_OPTIONS))) {
+            return true;
+        }
+        if self.absolute.is_empty() {
+            return false;
+        }
+        let absolute = match relative {
+            Some(relative) => self.absolute_root.join(relative),
+            None if path.is_absolute() => path.to_path_buf(),
+            None => return false,
+        };
+        self.absolute.iter().any(|p| p.matches_path_with(&absolute, MATCH_OPTIONS))
+    }
+    /**
+     Whether the dir is not searched into, by the ignore files of its parents or the global gitignore.
+     The global gitignore usually has the build dirs, so it only stops searching and never hides a target, see `is_ignored_target`.
+     */
+    pub fn is_ignored(&self, ignores: &IgnoreChain, dir: &Path) -> bool {
+        match ignores.matched(dir) {
+            Some(ignored) => ignored,
+            None => self.global_gitignore.as_ref().is_some_and(|g| g.matched(dir, true).is_ignore()),
+        }
+    }
+    /// Whether the target is hidden by the ignore files of its parents.
+    pub fn is_ignored_target(&self, ignores: &IgnoreChain, target: &Path) -> bool {
+        ignores.matched(target).unwrap_or(false)
+    }
+}
+
+/// The (device, inode) of the dir, which is the same for every path to it, so the dirs reached twice by the symlinks are searched once.
+#[cfg(unix)]
+pub fn dir_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn dir_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// The ignore files from a dir up to the root of the search, the deepest one first.
+#[derive(Clone, Default)]
+pub struct IgnoreChain(Option<Arc<IgnoreNode>>);
+
+struct IgnoreNode {
+    ignore: Gitignore,
+    parent: IgnoreChain,
+}
+
+impl IgnoreChain {
+    /// The chain of the dir, with its ignore file if it has one. The invalid lines of the file are skipped.
+    pub fn enter(&self, dir: &Path, has_file: bool) -> IgnoreChain {
+        if !has_file {
+            return self.clone();
+        }
+        let (ignore, _) = Gitignore::new(dir.join(IGNORE_FILE));
+        IgnoreChain(Some(Arc::new(IgnoreNode { ignore, parent: self.clone() })))
+    }
+    /// `Some(true)` if the dir is ignored, `Some(false)` if it is whitelisted by a `!` pattern. The deepest file which matches decides.
+    fn matched(&self, dir: &Path) -> Option<bool> {
+        let mut chain = self;
+        while let Some(node) = &chain.0 {
+            match node.ignore.matched(dir, true) {
+                Match::Ignore(_) => return Some(true),
+                Match::Whitelist(_) => return Some(false),
+                Match::None => chain = &node.parent,
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excluding(root: &Path, exclude: &[&str]) -> Traversal {
+        Traversal::new(root, &Scope { exclude: exclude.iter().map(|s| s.to_string()).collect(), ..Scope::default() }).unwrap()
+    }
+
+    #[test]
+    fn single_star_does_not_match_the_separator() {
+        let root = Path::new("/work");
+        let traversal = excluding(root, &["vendor/*", "**/fixtures"]);
+        assert!(traversal.is_excluded(&root.join("vendor/lib")));
+        assert!(!traversal.is_excluded(&root.join("vendor/lib/src")));
+        assert!(!traversal.is_excluded(&root.join("a/vendor/lib")));
+        assert!(traversal.is_excluded(&root.join("fixtures")));
+        assert!(traversal.is_excluded(&root.join("a/b/fixtures")));
+        assert!(!traversal.is_excluded(&root.join("a/b/fixtures2")));
+    }
+
+    #[test]
+    fn absolute_globs_match_the_absolute_path() {
+        let root = Path::new("/work");
+        let traversal = excluding(root, &["/work/backups/", "/mnt/*"]);
+        assert!(traversal.is_excluded(&root.join("backups")));
+        assert!(!traversal.is_excluded(&root.join("a/backups")));
+        // A target found by a resolver out of the root.
+        assert!(traversal.is_excluded(Path::new("/mnt/disk")));
+        assert!(!traversal.is_excluded(Path::new("/mnt/disk/target")));
+    }
+
+    #[test]
+    fn home_is_expanded() {
+        let Some(home) = env::var_os("HOME").map(PathBuf::from) else {
+            return;
+        };
+        let traversal = excluding(Path::new("/work"), &["~/Library"]);
+        assert!(traversal.is_excluded(&home.join("Library")));
+        assert!(!traversal.is_excluded(&home.join("src/Library")));
+        assert!(!traversal.is_excluded(Path::new("/work/Library")));
+    }
+
+    #[test]
+    fn default_root_is_matched_both_ways() {
+        let cwd = fs::canonicalize(".").unwrap();
+        let absolute = cwd.join("vendor").to_string_lossy().into_owned();
+        let traversal = excluding(Path::new("./"), &["vendor", absolute.as_str()]);
+        // The dirs are read under `./`, like `./vendor`.
+        assert!(traversal.is_excluded(Path::new("./vendor")));
+        assert!(!traversal.is_excluded(Path::new("./src")));
+        let traversal = excluding(Path::new("./"), &[absolute.as_str()]);
+        assert!(traversal.is_excluded(Path::new("./vendor")));
+        assert!(traversal.is_excluded(&cwd.join("vendor")));
+        assert!(!traversal.is_excluded(Path::new("./src/vendor")));
+    }
+
+    /// The chain of the dirs from the root down to `dirs`, reading their ignore files.
+    fn chain(root: &Path, dirs: &[&str]) -> IgnoreChain {
+        let mut chain = IgnoreChain::default().enter(root, root.join(IGNORE_FILE).is_file());
+        let mut dir = root.to_path_buf();
+        for name in dirs {
+            dir = dir.join(name);
+            chain = chain.enter(&dir, dir.join(IGNORE_FILE).is_file());
+        }
+        chain
+    }
+
+    #[test]
+    fn nested_ignore_file_whitelists() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        fs::create_dir_all(root.join("app/vendor/keep")).unwrap();
+        fs::write(root.join(IGNORE_FILE), "vendor/\nbuild\n").unwrap();
+        fs::write(root.join("app").join(IGNORE_FILE), "!vendor/\n").unwrap();
+        let traversal = excluding(root, &[]);
+        let top = chain(root, &[]);
+        assert!(traversal.is_ignored(&top, &root.join("vendor")));
+        assert!(!traversal.is_ignored(&top, &root.join("app")));
+        // The deeper file decides.
+        let app = chain(root, &["app"]);
+        assert!(!traversal.is_ignored(&app, &root.join("app/vendor")));
+        assert!(traversal.is_ignored(&app, &root.join("app/build")));
+        assert!(!traversal.is_ignored_target(&app, &root.join("app/vendor")));
+        assert!(traversal.is_ignored_target(&top, &root.join("vendor")));
+        // A sibling is not affected by the file of `app`.
+        fs::create_dir_all(root.join("lib/vendor")).unwrap();
+        assert!(traversal.is_ignored(&chain(root, &["lib"]), &root.join("lib/vendor")));
+    }
+}
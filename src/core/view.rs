@@ -0,0 +1,158 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use super::progress::Progress;
+use super::selector::{SelectOptions, SelectStatus};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Default)]
+pub enum SortMode {
+    /// The largest first.
+    Size,
+    #[default]
+    Path,
+    /// By the prefix of the rule, then by the path.
+    Rule,
+    /// The least recently worked project first.
+    Modified,
+}
+
+impl SortMode {
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Size => SortMode::Path,
+            SortMode::Path => SortMode::Rule,
+            SortMode::Rule => SortMode::Modified,
+            SortMode::Modified => SortMode::Size,
+        }
+    }
+    fn compare(self, a: &SelectOptions, b: &SelectOptions) -> Ordering {
+        let ordering = match self {
+            // The ones still calculating are put at the end.
+            SortMode::Size => b.size.cmp(&a.size),
+            SortMode::Path => Ordering::Equal,
+            SortMode::Rule => a.prefix.cmp(&b.prefix),
+            SortMode::Modified => match (a.modified, b.modified) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        };
+        ordering.then_with(|| a.path.cmp(&b.path))
+    }
+}
+
+impl fmt::Display for SortMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SortMode::Size => "size",
+            SortMode::Path => "path",
+            SortMode::Rule => "rule",
+            SortMode::Modified => "modified",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/**
+ How the options are shown. The options are never reordered, because the searching and removing threads refer to them by index.
+ Instead, `order` gives the indexes of the options in the order they should be shown.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct View {
+    pub sort: SortMode,
+    /// Only the options fuzzy matched by it are shown. Empty to show all.
+    pub filter: String,
+    /// The selected row of the sub-targets of the selected option, or `None` if they are not shown.
+    pub detail: Option<usize>,
+    /// The indexes of the options shown on the screen at the last refresh, whose sizes are calculated first.
+    pub visible: Arc<Mutex<Vec<usize>>>,
+    /// The search whose options are shown.
+    pub progress: Arc<Progress>,
+}
+
+impl View {
+    pub fn order(&self, options: &[SelectOptions]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..options.len()).filter(|i| !matches!(options[*i].status, SelectStatus::Hidden) && self.matches(&options[*i]).is_some()).collect();
+        order.sort_by(|a, b| self.sort.compare(&options[*a], &options[*b]));
+        order
+    }
+    /// The indexes of the matched chars in the prefix and in the path, or `None` if the option is filtered out.
+    pub fn matches(&self, option: &SelectOptions) -> Option<(Vec<usize>, Vec<usize>)> {
+        let matched = fuzzy_match(&self.filter, option.prefix.chars().chain(option.path.chars()))?;
+        let prefix_length = option.prefix.chars().count();
+        let (prefix, path): (Vec<usize>, Vec<usize>) = matched.into_iter().partition(|i| *i < prefix_length);
+        Some((prefix, path.into_iter().map(|i| i - prefix_length).collect()))
+    }
+}
+
+/// The numbers shown at the top of the selector. The sizes are the reclaimable ones and the hidden options are not counted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub matches: usize,
+    /// The size of the options which are not removed, and the size freed.
+    pub found: u64,
+    pub marked: usize,
+    pub marked_size: u64,
+    /// The size of the removed options and sub-targets.
+    pub freed: u64,
+    /// The options and sub-targets being removed.
+    pub removing: usize,
+}
+
+impl Stats {
+    pub fn of(options: &[SelectOptions]) -> Self {
+        let mut stats = Stats::default();
+        for option in options {
+            let size = option.size.unwrap_or(0);
+            match option.status {
+                SelectStatus::Hidden => continue,
+                SelectStatus::Deleted => stats.freed += size,
+                // A dir removing a sub-target is counted by the sub-target.
+                SelectStatus::Deleting if !option.subs.iter().any(|s| matches!(s.status, SelectStatus::Deleting)) => stats.removing += 1,
+                _ => {}
+            }
+            stats.matches += 1;
+            if option.marked {
+                stats.marked += 1;
+                stats.marked_size += size;
+            }
+            for sub in &option.subs {
+                match sub.status {
+                    SelectStatus::Deleted => stats.freed += sub.size.unwrap_or(0),
+                    SelectStatus::Deleting => stats.removing += 1,
+                    _ => {}
+                }
+            }
+            if !matches!(option.status, SelectStatus::Deleted) {
+                stats.found += size;
+            }
+        }
+        stats.found += stats.freed;
+        stats
+    }
+}
+
+/// Match the chars of the query in order, ignoring the case. Returns the indexes of the matched chars of the text.
+fn fuzzy_match(query: &str, text: impl Iterator<Item = char>) -> Option<Vec<usize>> {
+    let mut query = query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).peekable();
+    let mut matched = vec![];
+    for (i, c) in text.enumerate() {
+        match query.peek() {
+            Some(q) if c.to_lowercase().eq(std::iter::once(*q)) => {
+                matched.push(i);
+                query.next();
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+    if query.peek().is_none() {
+        Some(matched)
+    } else {
+        None
+    }
+}
@@ -1,27 +1,136 @@
 mod core;
 mod render;
-use core::selector::Selector;
+use core::checker::get_config_chain;
+use core::filter::{parse_duration, parse_size, Filter};
+use core::remover::DeleteMode;
+use core::migration::{outdated_configs, upgrade_config, CONFIG_VERSION};
+use core::selector::{SelectOptions, SelectStatus, Selector};
+use core::traversal::{parse_exclude, Scope};
+use core::view::SortMode;
+use render::printer::{print_clean, print_list, OutputFormat};
 use render::renderer::exit;
-use std::io::stdout;
+use std::io::{stdin, stdout, IsTerminal};
 
 /// Search for a pattern in a file and display the lines that contain it.
-use clap::Parser;
+use clap::{Parser, Subcommand};
+
+/// The filters of the subcommands. All of them should be matched.
+#[derive(clap::Args, Debug)]
+struct FilterArgs {
+    /// Only the targets of the rule, by its prefix (e.g. "node" or "[Node]") or its target name. Can be repeated.
+    #[clap(short, long, value_parser)]
+    rule: Vec<String>,
+    /// Only the targets which are not smaller than the size, e.g. "500M" or "1G".
+    #[clap(long, value_parser = parse_size)]
+    min_size: Option<u64>,
+    /// Only the targets whose project is not modified in the duration, e.g. "30d", "2w" or "6mo".
+    #[clap(long, value_parser = parse_duration)]
+    older_than: Option<std::time::Duration>,
+    /// Only the targets whose path matches the glob, e.g. "*/archive/*".
+    #[clap(long, value_parser)]
+    path_glob: Option<glob::Pattern>,
+}
+
+impl FilterArgs {
+    fn to_filter(&self) -> Filter {
+        Filter { rules: self.rule.clone(), min_size: self.min_size, older_than: self.older_than, path_glob: self.path_glob.clone() }
+    }
+}
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
     /// The path that start to check dirs. If should contain many dirs.
-   #[clap(short, long, value_parser, default_value = "./")]
-    path:std::path::PathBuf
+   #[clap(short, long, value_parser, default_value = "./", global = true)]
+    path:std::path::PathBuf,
+    /// How to remove the dirs. It overrides `delete_mode` of the config.
+    #[clap(long, value_enum, global = true)]
+    delete_mode: Option<DeleteMode>,
+    /// The order of the dirs. Type [s] in the selector to change it.
+    #[clap(long, value_enum, default_value = "path", global = true)]
+    sort: SortMode,
+    /// The config file with the highest priority. It is merged over `$KMALL_CONFIG`, `$XDG_CONFIG_HOME/kmall/config.yaml` and the one next to the executable.
+    #[clap(long, value_parser, global = true)]
+    config: Option<std::path::PathBuf>,
+    /// The levels of the dirs under the path which are searched, 0 searches only the path itself. It overrides `max_depth` of the config.
+    #[clap(long, value_parser, global = true)]
+    max_depth: Option<usize>,
+    /// A glob of the paths which are not searched into, relative to the path (e.g. "vendor/*" or "**/fixtures"), or absolute if it starts with "/" or "~/". Can be repeated, and added to `exclude` of the config.
+    #[clap(long, value_parser = parse_exclude, global = true)]
+    exclude: Vec<String>,
+    /// Search into the symlinked dirs too. Each dir is searched once even if the symlinks make a loop.
+    #[clap(long, global = true)]
+    follow_symlinks: bool,
+    /// Do not search into the dirs on another file system than the path, like mounted disks.
+    #[clap(long, global = true)]
+    one_file_system: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the matched dirs without opening the selector. It waits until all sizes are calculated.
+    List {
+        /// The output format.
+        #[clap(short, long, value_enum, default_value = "plain")]
+        format: OutputFormat,
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// Remove the matched dirs without opening the selector. Use the filters to choose what to remove.
+    Clean {
+        #[clap(flatten)]
+        filter: FilterArgs,
+        /// Print what would be removed and the total bytes, without removing anything.
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
 
-fn run(path:std::path::PathBuf){
-    let mut stdout = stdout();
+
+/// Load the config and start searching, with the options of the arguments. Exit if the config can not be loaded, before the selector is shown.
+fn start_selector(args: &Args) -> Selector {
     let mut menu_ui = Selector::new(0);
-    if menu_ui.init(path)==false{
-        return;
+    let scope = Scope { max_depth: args.max_depth, exclude: args.exclude.clone(), follow_symlinks: args.follow_symlinks, one_file_system: args.one_file_system, ..Scope::default() };
+    if let Err(e) = menu_ui.init(args.path.clone(), args.config.as_deref(), &scope) {
+        eprintln!("Can not load the config.\n{}", e);
+        std::process::exit(1);
     };
+    if let Some(mode) = &args.delete_mode {
+        menu_ui.delete_mode = mode.clone();
+    }
+    menu_ui.view.sort = args.sort;
+    menu_ui
+}
+
+/**
+ Offer to write the config files of older versions back after upgrading them, before the selector is shown.
+ Without a terminal, or in the subcommands, it only prints a note, so nothing waits for an answer.
+ */
+fn offer_upgrade(args: &Args, interactive: bool) {
+    let files: Vec<std::path::PathBuf> = get_config_chain(args.config.as_deref()).into_iter().filter(|p| p.is_file()).collect();
+    for (path, version) in outdated_configs(&files) {
+        if !interactive {
+            eprintln!("The config {} is version {}, it is upgraded to version {} in memory. Run kmall in a terminal without a subcommand to write it back.", path.display(), version, CONFIG_VERSION);
+            continue;
+        }
+        eprint!("The config {} is version {}. Upgrade it to version {}? The old file will be backed up. [y/N] ", path.display(), version, CONFIG_VERSION);
+        let mut answer = String::new();
+        if stdin().read_line(&mut answer).is_err() || !answer.trim().eq_ignore_ascii_case("y") {
+            continue;
+        }
+        match upgrade_config(&path) {
+            Ok(backup) => eprintln!("Upgraded. The old one is kept as {}.", backup.display()),
+            Err(e) => eprintln!("Can not upgrade the config.\n{}", e),
+        }
+    }
+}
+
+fn run(args: &Args){
+    let mut stdout = stdout();
+    offer_upgrade(args, stdin().is_terminal());
+    let mut menu_ui = start_selector(args);
     loop {
         let res: usize = menu_ui.render(&mut stdout);
         if res == usize::MAX {
@@ -35,7 +144,43 @@ fn run(path:std::path::PathBuf){
     }
 }
 
+fn run_list(args: &Args, format: &OutputFormat, filter: &Filter){
+    offer_upgrade(args, false);
+    let menu_ui = start_selector(args);
+    let options = menu_ui.wait();
+    let sorted: Vec<SelectOptions> = menu_ui.view.order(&options).into_iter().filter(|i| filter.matches(&options[*i])).map(|i| options[i].clone()).collect();
+    if let Err(e) = print_list(&sorted, format) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run_clean(args: &Args, filter: &Filter, dry_run: bool){
+    offer_upgrade(args, false);
+    let mut menu_ui = start_selector(args);
+    let options = menu_ui.wait();
+    let indexes: Vec<usize> = menu_ui.view.order(&options).into_iter().filter(|i| filter.matches(&options[*i])).collect();
+    if !dry_run {
+        for i in &indexes {
+            menu_ui.remove_file(*i);
+        }
+    }
+    let options = if dry_run { options } else { menu_ui.wait_remove() };
+    let selected: Vec<SelectOptions> = indexes.iter().map(|i| options[*i].clone()).collect();
+    if let Err(e) = print_clean(&selected, &menu_ui.delete_mode, dry_run) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    if selected.iter().any(|o| matches!(o.status, SelectStatus::Failed)) {
+        std::process::exit(1);
+    }
+}
+
 fn main() {
     let args = Args::parse();
-    run(args.path);
+    match &args.command {
+        Some(Command::List { format, filter }) => run_list(&args, format, &filter.to_filter()),
+        Some(Command::Clean { filter, dry_run }) => run_clean(&args, &filter.to_filter(), *dry_run),
+        None => run(&args),
+    }
 }
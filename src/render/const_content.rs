@@ -29,10 +29,16 @@ pub fn get_help()->String{
     Controls:
      - [q] to quit. [f]/[b] jump to next/previous 10 options. 
      - [j]/[Arrow Down] select next 1 option. [k]/[Arrow Up] select previous 1 option 
+     - [Space]/[Enter] remove the selected dir. Confirm with [y], or type the dir name if it is large.
+     - [m] mark/unmark the selected dir. [a] mark all. [r] mark all of the selected rule. [i] invert the marks. [u] unmark all.
+     - [d] remove all the marked dirs.
+     - [v] show the sub-targets of the selected dir. [Space]/[Enter] remove the selected one instead of the whole dir.
+     - [s] change the order: by size, path, rule or modified time.
+     - [/] filter the dirs by fuzzy matching the path and prefix. [Enter] to keep the filter, [Esc] to clear it.
      will add more controls in the future
     Input:
      - "-open" open the dir with finder/explorer.
-     - "-config" open the config dir with finder/explorer.
+     - "-config" open the dir of the config file with the highest priority with finder/explorer.
     "#;
-    return content.to_owned();
+    content.to_owned()
 }
\ No newline at end of file
@@ -1,2 +1,3 @@
 pub mod renderer;
-pub mod const_content;
\ No newline at end of file
+pub mod const_content;
+pub mod printer;
\ No newline at end of file
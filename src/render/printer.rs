@@ -0,0 +1,127 @@
+use std::{
+    io::{stdout, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::core::remover::DeleteMode;
+use crate::core::selector::{SelectOptions, SelectStatus};
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum OutputFormat {
+    Plain,
+    Json,
+    Ndjson,
+}
+
+#[derive(Serialize)]
+struct ListEntry<'a> {
+    path: &'a str,
+    project: &'a str,
+    prefix: &'a str,
+    target: &'a str,
+    /// The bytes freed by removing it.
+    size: Option<u64>,
+    /// The sum of the file lengths.
+    apparent_size: Option<u64>,
+    /// Unix timestamp in seconds.
+    modified: Option<u64>,
+    git: Option<GitEntry>,
+    protected: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sub_targets: Vec<SubEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct SubEntry<'a> {
+    name: &'a str,
+    size: Option<u64>,
+    apparent_size: Option<u64>,
+    paths: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct GitEntry {
+    /// Unix timestamp in seconds.
+    last_commit: Option<u64>,
+    dirty: bool,
+}
+
+fn unix_seconds(time: Option<SystemTime>) -> Option<u64> {
+    time.and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_secs())
+}
+
+impl<'a> ListEntry<'a> {
+    fn new(option: &'a SelectOptions) -> Self {
+        let git = option.git.as_ref().map(|g| GitEntry { last_commit: unix_seconds(g.last_commit), dirty: g.dirty });
+        let protected = matches!(option.status, SelectStatus::Protected);
+        let sub_targets = option.subs.iter().map(|s| SubEntry { name: &s.name, size: s.size, apparent_size: s.apparent, paths: s.paths.iter().map(|p| p.to_string_lossy().into_owned()).collect() }).collect();
+        ListEntry { path: &option.path, project: &option.project, prefix: &option.prefix, target: &option.target, size: option.size, apparent_size: option.apparent, modified: unix_seconds(option.modified), git, protected, sub_targets }
+    }
+}
+
+/**
+ Plain output is one match per line with tab separated `size prefix target path`, so it can be piped into `cut` or `awk`. The size is the reclaimable one.
+ JSON also has the project dir, when it was modified, the git status, whether it is protected and the sub-targets found.
+ A size which could not be calculated is printed as `-` (or `null` in JSON).
+ */
+pub fn print_list(options: &[SelectOptions], format: &OutputFormat) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let mut stdout = stdout().lock();
+    match format {
+        OutputFormat::Plain => {
+            for option in options {
+                let size = option.size.map_or("-".to_owned(), |s| s.to_string());
+                writeln!(stdout, "{}\t{}\t{}\t{}", size, option.prefix, option.target, option.path)?;
+            }
+        }
+        OutputFormat::Json => {
+            let entries: Vec<ListEntry> = options.iter().map(ListEntry::new).collect();
+            serde_json::to_writer_pretty(&mut stdout, &entries)?;
+            writeln!(stdout)?;
+        }
+        OutputFormat::Ndjson => {
+            for option in options {
+                serde_json::to_writer(&mut stdout, &ListEntry::new(option))?;
+                writeln!(stdout)?;
+            }
+        }
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/**
+ Print the result of `clean`, one target per line with tab separated `state size path`, then a summary of the total bytes.
+ With `dry_run` nothing has been removed, so every target is printed as `would-remove`. The protected ones are never removed.
+ The removed targets are printed as `trashed` with the `trash` delete mode, and `removed` otherwise.
+ */
+pub fn print_clean(options: &[SelectOptions], mode: &DeleteMode, dry_run: bool) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let mut stdout = stdout().lock();
+    let mut total: u64 = 0;
+    let mut count = 0;
+    let (state, verb) = match mode {
+        DeleteMode::Permanent => ("removed", "Removed"),
+        DeleteMode::Trash => ("trashed", "Trashed"),
+    };
+    for option in options {
+        let size = option.size.unwrap_or(0);
+        if let SelectStatus::Protected = option.status {
+            writeln!(stdout, "protected\t{}\t{}", size, option.path)?;
+            continue;
+        } else if dry_run {
+            writeln!(stdout, "would-remove\t{}\t{}", size, option.path)?;
+        } else if let SelectStatus::Deleted = option.status {
+            writeln!(stdout, "{}\t{}\t{}", state, size, option.path)?;
+        } else {
+            writeln!(stdout, "failed\t{}\t{}\t{}", size, option.path, option.end)?;
+            continue;
+        }
+        total += size;
+        count += 1;
+    }
+    let verb = if dry_run { "Would remove" } else { verb };
+    writeln!(stdout, "{} {} dirs, {} bytes ({:.2}Mb) in total.", verb, count, total, total as f64 / 1024_f64 / 1024_f64)?;
+    stdout.flush()?;
+    Ok(())
+}
@@ -14,8 +14,10 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use unicode_width::UnicodeWidthStr;
 
-use crate::core::checker::get_default_config_path;
+use crate::core::checker::ConfirmConfig;
 use crate::core::selector::{RendererOperation, SelectOptions, SelectStatus};
+use crate::core::progress::Progress;
+use crate::core::view::{Stats, View};
 use crate::render::const_content::get_bye;
 
 use super::const_content::{get_banner, get_bottom_tips, get_help};
@@ -26,9 +28,6 @@ pub struct StyledContent {
     pub back_color: Color,
 }
 impl StyledContent {
-    pub fn empty() -> Self {
-        return StyledContent { content: "".to_owned(), front_color: Color::Reset, back_color: Color::Reset };
-    }
     pub fn print(&self, stdout: &mut Stdout) {
         queue!(stdout, SetForegroundColor(self.front_color), SetBackgroundColor(self.back_color), Print(&self.content), ResetColor).unwrap();
     }
@@ -44,261 +43,594 @@ struct FixedContent {
 
 fn to_target_length(s: String, target_length: usize) -> (String, usize) {
     let real_length = UnicodeWidthStr::width(&s[0..]);
-    if target_length == real_length || target_length == real_length - 1 || target_length > real_length {
-        return (s, real_length);
+    if target_length + 1 >= real_length {
+        (s, real_length)
     } else {
         let half = (real_length + target_length) / 2;
         let res: String = s.char_indices().take(half).map(|(_, i)| i).collect();
-        return to_target_length(res, target_length);
+        to_target_length(res, target_length)
+    }
+}
+
+/// Fit the end of a row, like `[git 3 days ago][5 days ago][1.2Gb]`, in `max_width`: the leading parts (the git status, then the age) are dropped first, and the size is cut at last.
+fn fit_end(end: &str, max_width: usize) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    let mut start = 0;
+    for (i, _) in end.match_indices('[').skip(1) {
+        parts.push(&end[start..i]);
+        start = i;
+    }
+    parts.push(&end[start..]);
+    while parts.len() > 1 && parts.iter().map(|p| UnicodeWidthStr::width(*p)).sum::<usize>() > max_width {
+        parts.remove(0);
     }
+    to_target_length(parts.concat(), max_width.saturating_sub(1)).0
 }
 
 fn file_fix_length(max_col: u16, path: &str, prefix: &str, end: &str) -> FixedContent {
-    if UnicodeWidthStr::width(prefix) + UnicodeWidthStr::width(path) + UnicodeWidthStr::width(end) > max_col as usize {
-        let target_length = (max_col as i64 - (max_col as i64 / 4) * 3) - end.len() as i64;
+    let max_col = max_col as usize;
+    let prefix_length = UnicodeWidthStr::width(prefix);
+    if prefix_length + UnicodeWidthStr::width(path) + UnicodeWidthStr::width(end) > max_col {
         let space_length = 2;
-        //it may overflow?
-        let (head, head_length) = to_target_length(prefix.to_owned() + path, target_length as usize);
+        let end = fit_end(end, (max_col / 2).saturating_sub(prefix_length));
+        let end_length = UnicodeWidthStr::width(&end[..]);
+        // the head keeps the whole prefix, even if there is no room for the path
+        let target_length = (max_col - (max_col / 4) * 3).saturating_sub(end_length).max(prefix_length);
+        let (head, head_length) = to_target_length(prefix.to_owned() + path, target_length);
         let reversed: String = path.char_indices().rev().map(|(_, i)| i).collect();
-        let (tail_reversed, tail_length) = to_target_length(reversed, max_col as usize - head_length - space_length - end.len() - 4);
+        let (tail_reversed, tail_length) = to_target_length(reversed, max_col.saturating_sub(head_length + space_length + end_length + 4));
         let tail: String = tail_reversed.char_indices().rev().map(|(_, i)| i).collect();
-        // let tail_width = UnicodeWidthStr::width(&tail[0..]);
+        let path = head.strip_prefix(prefix).unwrap_or("").to_owned();
 
-        return FixedContent { space: " ".repeat(space_length), point: ".".repeat(max_col as usize - head_length - tail_length - end.len() - space_length), path: head.replace(prefix, ""), prefix: prefix.to_owned(), end: end.to_owned(), tail };
+        return FixedContent { space: " ".repeat(space_length), point: ".".repeat(max_col.saturating_sub(head_length + tail_length + end_length + space_length)), path, prefix: prefix.to_owned(), end, tail };
     }
-    return FixedContent { space: " ".repeat(max_col as usize - UnicodeWidthStr::width(prefix) - UnicodeWidthStr::width(path) - UnicodeWidthStr::width(end)), point: "".to_owned(), tail: "".to_owned(), path: path.to_owned(), prefix: prefix.to_owned(), end: end.to_owned() };
+    FixedContent { space: " ".repeat(max_col - prefix_length - UnicodeWidthStr::width(path) - UnicodeWidthStr::width(end)), point: "".to_owned(), tail: "".to_owned(), path: path.to_owned(), prefix: prefix.to_owned(), end: end.to_owned() }
 }
 
-fn print_content(stdout: &mut Stdout, option: &SelectOptions, fixed_content: FixedContent, selected: bool) {
-    let content = fixed_content.path + &fixed_content.point + &fixed_content.tail + &fixed_content.space + &fixed_content.end + "\r\n"; //#TODO can make it more flexible
-    let prefix_printer = StyledContent { content: fixed_content.prefix.clone(), front_color: Color::Yellow, back_color: Color::Reset };
-    let mut content_printer = StyledContent { content: content.clone(), front_color: Color::Reset, back_color: Color::Reset };
-    match option.status {
-        SelectStatus::Live | SelectStatus::Calculating | SelectStatus::Searched => {
-            prefix_printer.print(stdout);
-            if selected == true {
-                content_printer.back_color = Color::Blue;
-            }
-            content_printer.print(stdout);
-        }
-        SelectStatus::Deleted => {
-            prefix_printer.print(stdout);
-            content_printer.front_color = Color::Red;
-            if selected == true {
-                content_printer.back_color = Color::Grey;
-            }
-            content_printer.print(stdout);
+/// Print the chars of the text, those whose index (counted from `start`) is in `matched` are highlighted.
+fn print_highlighted(stdout: &mut Stdout, text: &str, start: usize, matched: &[usize], style: &StyledContent) {
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in text.chars().enumerate() {
+        let is_matched = matched.contains(&(start + i));
+        if is_matched != run_matched && !run.is_empty() {
+            let front_color = if run_matched { Color::Magenta } else { style.front_color };
+            StyledContent { content: std::mem::take(&mut run), front_color, back_color: style.back_color }.print(stdout);
         }
-        SelectStatus::Deleting => {
-            prefix_printer.print(stdout);
-            content_printer.front_color = Color::DarkYellow;
-            if selected == true {
-                content_printer.back_color = Color::Green;
-            }
-            content_printer.print(stdout);
-        }
-        SelectStatus::System => {
-            content_printer.front_color = Color::Cyan;
-            if selected == true {
-                content_printer.back_color = Color::Blue;
+        run_matched = is_matched;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        let front_color = if run_matched { Color::Magenta } else { style.front_color };
+        StyledContent { content: run, front_color, back_color: style.back_color }.print(stdout);
+    }
+}
+
+fn print_content(stdout: &mut Stdout, option: &SelectOptions, fixed_content: FixedContent, selected: bool, matched: &(Vec<usize>, Vec<usize>)) {
+    let prefix_color = if option.marked { Color::Green } else { Color::Yellow };
+    let prefix_printer = StyledContent { content: fixed_content.prefix.clone(), front_color: prefix_color, back_color: Color::Reset };
+    let (front_color, back_color) = match option.status {
+        SelectStatus::Live | SelectStatus::Searched => (Color::Reset, if selected { Color::Blue } else { Color::Reset }),
+        SelectStatus::Deleted | SelectStatus::Failed => (Color::Red, if selected { Color::Grey } else { Color::Reset }),
+        SelectStatus::Deleting => (Color::DarkYellow, if selected { Color::Green } else { Color::Reset }),
+        SelectStatus::Protected | SelectStatus::Hidden => (Color::Cyan, if selected { Color::DarkGrey } else { Color::Reset }),
+    };
+    let content_printer = StyledContent { content: fixed_content.point.clone(), front_color, back_color };
+    // The mark is put before the prefix, so the matched indexes of the prefix are shifted.
+    let mark_length = fixed_content.prefix.chars().count().saturating_sub(option.prefix.chars().count());
+    let (prefix_matched, path_matched) = matched;
+    let prefix_matched: Vec<usize> = prefix_matched.iter().map(|i| i + mark_length).collect();
+    print_highlighted(stdout, &fixed_content.prefix, 0, &prefix_matched, &prefix_printer);
+    print_highlighted(stdout, &fixed_content.path, 0, path_matched, &content_printer);
+    content_printer.print(stdout);
+    let path_length = option.path.chars().count();
+    let tail_length = fixed_content.tail.chars().count();
+    print_highlighted(stdout, &fixed_content.tail, path_length - tail_length, path_matched, &content_printer);
+    let end_printer = StyledContent { content: fixed_content.space + &fixed_content.end + "\r\n", front_color, back_color };
+    end_printer.print(stdout);
+}
+
+/// The position of the selected option in the order. If it is not shown, select the first one.
+fn selected_position(order: &[usize], selected: &mut usize) -> usize {
+    match order.iter().position(|i| i == selected) {
+        Some(position) => position,
+        None => {
+            if let Some(first) = order.first() {
+                *selected = *first;
             }
-            content_printer.print(stdout);
+            0
         }
     }
 }
 
-pub fn refresh_selector(stdout: &mut Stdout, _options: Arc<Mutex<Vec<SelectOptions>>>, selected: &mut usize, top_content: &StyledContent, bottom_content: &StyledContent) {
+pub fn refresh_selector(stdout: &mut Stdout, _options: Arc<Mutex<Vec<SelectOptions>>>, selected: &mut usize, view: &View, top_content: &StyledContent, bottom_content: &StyledContent) {
     queue!(stdout, terminal::Clear(terminal::ClearType::UntilNewLine), cursor::MoveTo(0, 0), cursor::Hide).unwrap();
     top_content.print(stdout);
     let mut row = 0;
     let (_max_col, _max_row) = crossterm::terminal::size().unwrap();
-    let max_row = _max_row as usize - top_content.content.match_indices("\r\n").count(); //remain rows after counting the top_content
+    let max_row = _max_row as usize - top_content.content.match_indices("\r\n").count() - 1; //remain rows after counting the top_content and the stats
     let options = _options.lock().unwrap();
-    for i in 0..options.len() {
-        let fixed_content = file_fix_length(_max_col, &options[i].path, &options[i].prefix, &options[i].end);
-        if *selected == i {
-            print_content(stdout, &options[i], fixed_content, true);
-        } else {
-            if options.len() > max_row - 1 {
-                // if i>*selected+((max_row as usize)-row-2){
-                //     return;
-                // }
-                if (*selected) as i64 - ((max_row / 2) as i64) < i as i64 && (*selected) as i64 > i as i64 {
-                    print_content(stdout, &options[i], fixed_content, false);
-                    row += 1;
-                } else if *selected + ((max_row as usize) - row - 1) > i && *selected < i {
-                    print_content(stdout, &options[i], fixed_content, false);
-                } else if *selected + ((max_row as usize) - row - 1) <= i {
-                    break;
-                }
-            } else {
-                print_content(stdout, &options[i], fixed_content, false);
+    let stats = Stats::of(&options);
+    print_stats(stdout, &stats, &view.progress, options.get(*selected), _max_col as usize);
+    let order = view.order(&options);
+    let current = selected_position(&order, selected);
+    let mut visible: Vec<usize> = vec![];
+    for (position, &i) in order.iter().enumerate() {
+        let prefix = if options[i].marked { "*".to_owned() + &options[i].prefix } else { options[i].prefix.clone() };
+        let fixed_content = file_fix_length(_max_col, &options[i].path, &prefix, &options[i].end);
+        let matched = view.matches(&options[i]).unwrap_or_default();
+        if current == position {
+            print_content(stdout, &options[i], fixed_content, true, &matched);
+            visible.push(i);
+        } else if order.len() > max_row - 1 {
+            if current as i64 - ((max_row / 2) as i64) < position as i64 && current > position {
+                print_content(stdout, &options[i], fixed_content, false, &matched);
+                visible.push(i);
+                row += 1;
+            } else if current + (max_row - row - 1) > position && current < position {
+                print_content(stdout, &options[i], fixed_content, false, &matched);
+                visible.push(i);
+            } else if current + (max_row - row - 1) <= position {
+                break;
             }
+        } else {
+            print_content(stdout, &options[i], fixed_content, false, &matched);
+            visible.push(i);
         }
     }
 
+    *view.visible.lock().unwrap() = visible;
     queue!(stdout, terminal::Clear(terminal::ClearType::FromCursorDown),).unwrap();
 
-    if bottom_content.content != "" {
+    if !bottom_content.content.is_empty() {
         bottom_content.print(stdout);
     }
+    let sort_content = StyledContent { content: format!(" [s] Sort by {}.", view.sort), front_color: Color::DarkGrey, back_color: Color::Reset };
+    sort_content.print(stdout);
+    if !view.filter.is_empty() {
+        let filter_content = StyledContent { content: format!(" Filter \"{}\": {}/{}. [Esc] to clear.", view.filter, order.len(), options.len()), front_color: Color::Magenta, back_color: Color::Reset };
+        filter_content.print(stdout);
+    }
+    if let Some(count) = options.get(*selected).map(|o| o.subs.len()).filter(|c| *c > 0) {
+        let detail_content = StyledContent { content: format!(" [v] {} sub-targets.", count), front_color: Color::DarkGrey, back_color: Color::Reset };
+        detail_content.print(stdout);
+    }
+    if stats.marked > 0 {
+        let marked_content = StyledContent { content: format!(" Marked {} dirs, {}. [d] to remove them.", stats.marked, format_mb(stats.marked_size)), front_color: Color::Green, back_color: Color::Reset };
+        marked_content.print(stdout);
+    }
     stdout.flush().unwrap();
 }
 
-fn select_down(stdout: &mut Stdout, _options: Arc<Mutex<Vec<SelectOptions>>>, selected: &mut usize, size: usize, title: &StyledContent, bottom: &StyledContent) {
+fn format_mb(size: u64) -> String {
+    format!("{:.2}Mb", size as f64 / 1024_f64 / 1024_f64)
+}
+
+/// The status line under the banner, like `Done: 120 dirs in 0.5s | Matches: 12 | Found: 1.20Mb | Selected: 0.30Mb | Freed: 0.50Mb | Removing: 1`.
+fn print_stats(stdout: &mut Stdout, stats: &Stats, progress: &Progress, selected: Option<&SelectOptions>, max_col: usize) {
+    let elapsed = progress.elapsed().as_secs_f64();
+    let scan = if progress.is_done() { format!("Done: {} dirs in {:.1}s", progress.scanned(), elapsed) } else { format!("Scanning: {} dirs, {:.1}s", progress.scanned(), elapsed) };
+    let mut parts = vec![scan, format!("Matches: {}", stats.matches), format!("Found: {}", format_mb(stats.found))];
+    if stats.marked > 0 {
+        parts.push(format!("Marked {}: {}", stats.marked, format_mb(stats.marked_size)));
+    } else if let Some(size) = selected.and_then(|o| o.size) {
+        parts.push(format!("Selected: {}", format_mb(size)));
+    }
+    parts.push(format!("Freed: {}", format_mb(stats.freed)));
+    if stats.removing > 0 {
+        parts.push(format!("Removing: {}", stats.removing));
+    }
+    let (line, _) = to_target_length(parts.join(" | "), max_col.saturating_sub(1));
+    let content = StyledContent { content: line, front_color: Color::Cyan, back_color: Color::Reset };
+    content.print(stdout);
+    queue!(stdout, terminal::Clear(terminal::ClearType::UntilNewLine), Print("\r\n")).unwrap();
+}
+
+fn select_down(stdout: &mut Stdout, _options: Arc<Mutex<Vec<SelectOptions>>>, selected: &mut usize, view: &View, size: usize, title: &StyledContent, bottom: &StyledContent) {
     {
         let options = _options.lock().unwrap();
-        if options.len() == 0 {
+        let order = view.order(&options);
+        if order.is_empty() {
             return;
         }
-        if *selected as i64 >= options.len() as i64 - size as i64 {
-            *selected = 0;
+        let current = selected_position(&order, selected);
+        if current as i64 >= order.len() as i64 - size as i64 {
+            *selected = order[0];
         } else {
-            *selected += size;
+            *selected = order[current + size];
         }
     }
-    refresh_selector(stdout, _options, selected, title, bottom)
+    refresh_selector(stdout, _options, selected, view, title, bottom)
 }
-fn select_up(stdout: &mut Stdout, _options: Arc<Mutex<Vec<SelectOptions>>>, selected: &mut usize, size: usize, title: &StyledContent, bottom: &StyledContent) {
+fn select_up(stdout: &mut Stdout, _options: Arc<Mutex<Vec<SelectOptions>>>, selected: &mut usize, view: &View, size: usize, title: &StyledContent, bottom: &StyledContent) {
     {
         let options = _options.lock().unwrap();
-        if options.len() == 0 {
+        let order = view.order(&options);
+        if order.is_empty() {
             return;
         }
-        if *selected as i64 - size as i64 <= -1 {
-            *selected = options.len() - 1;
+        let current = selected_position(&order, selected);
+        if current as i64 - size as i64 <= -1 {
+            *selected = order[order.len() - 1];
         } else {
-            *selected -= size;
+            *selected = order[current - size];
         }
     }
-    refresh_selector(stdout, _options, selected, title, bottom)
+    refresh_selector(stdout, _options, selected, view, title, bottom)
 }
-pub fn selector(stdout: &mut Stdout, options: Arc<Mutex<Vec<SelectOptions>>>, selected: &mut usize, need_refresh: Arc<AtomicBool>) -> (usize, RendererOperation) {
-    let top_content = StyledContent { content: get_banner(), front_color: Color::Magenta, back_color: Color::Reset };
-    let input = String::new();
+/// The dialog shown before removing dirs. `word` should be typed to confirm when `need_type` is true.
+struct Confirm {
+    operation: (usize, RendererOperation),
+    title: String,
+    rows: Vec<(&'static str, String)>,
+    word: String,
+    need_type: bool,
+    typed: String,
+}
+
+impl Confirm {
+    /// Only the dir which can be removed needs a confirm. `None` if the selected one is still calculating or removed.
+    fn new(options: &Arc<Mutex<Vec<SelectOptions>>>, index: usize, config: &ConfirmConfig) -> Option<Self> {
+        let guard = options.lock().unwrap();
+        let option = guard.get(index)?;
+        if let SelectStatus::Live = option.status {
+            let name = Path::new(&option.path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let rows = vec![("Rule", option.prefix.clone()), ("Path", option.path.clone()), ("Size", format_size(option.size, option.apparent))];
+            return Some(Confirm { operation: (index, RendererOperation::Remove), title: "Remove this dir?".to_owned(), rows, word: name, need_type: need_type(config, option.size), typed: String::new() });
+        }
+        None
+    }
+    /// Confirm removing a sub-target of the dir. `None` if either of them can not be removed.
+    fn sub(options: &Arc<Mutex<Vec<SelectOptions>>>, index: usize, sub: usize, config: &ConfirmConfig) -> Option<Self> {
+        let guard = options.lock().unwrap();
+        let option = guard.get(index)?;
+        let sub_option = option.subs.get(sub)?;
+        if !matches!(option.status, SelectStatus::Live) || !matches!(sub_option.status, SelectStatus::Live) {
+            return None;
+        }
+        let rows = vec![("Rule", option.prefix.clone()), ("Path", option.path.clone()), ("Sub-target", sub_option.name.clone()), ("Dirs", sub_option.paths.len().to_string()), ("Size", format_size(sub_option.size, sub_option.apparent))];
+        Some(Confirm { operation: (index, RendererOperation::RemoveSub(sub)), title: "Remove this sub-target?".to_owned(), rows, word: sub_option.name.clone(), need_type: need_type(config, sub_option.size), typed: String::new() })
+    }
+    /// Confirm removing all the marked dirs which can be removed. `None` if there is not any.
+    fn marked(options: &Arc<Mutex<Vec<SelectOptions>>>, config: &ConfirmConfig) -> Option<Self> {
+        let guard = options.lock().unwrap();
+        let marked: Vec<&SelectOptions> = guard.iter().filter(|o| o.marked && matches!(o.status, SelectStatus::Live)).collect();
+        if marked.is_empty() {
+            return None;
+        }
+        let size: u64 = marked.iter().filter_map(|o| o.size).sum();
+        let apparent: u64 = marked.iter().filter_map(|o| o.apparent).sum();
+        let rows = vec![("Dirs", marked.len().to_string()), ("Size", format_size(Some(size), Some(apparent)))];
+        Some(Confirm { operation: (0, RendererOperation::RemoveMarked), title: "Remove all the marked dirs?".to_owned(), rows, word: "yes".to_owned(), need_type: need_type(config, Some(size)), typed: String::new() })
+    }
+}
+
+fn need_type(config: &ConfirmConfig, size: Option<u64>) -> bool {
+    match (config.type_name_above, size) {
+        (Some(threshold), Some(size)) => size >= threshold,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+/// The reclaimable size, with the apparent size beside it.
+fn format_size(size: Option<u64>, apparent: Option<u64>) -> String {
+    match (size, apparent) {
+        (Some(size), Some(apparent)) => format!("{:.2}Mb ({} bytes) freed, {:.2}Mb apparent", size as f64 / 1024_f64 / 1024_f64, size, apparent as f64 / 1024_f64 / 1024_f64),
+        _ => "unknown".to_owned(),
+    }
+}
+
+/// Keep the end of the text, which is the most meaningful part of a path.
+fn fit_tail(s: &str, width: usize) -> String {
+    if UnicodeWidthStr::width(s) <= width {
+        return s.to_owned();
+    }
+    let reversed: String = s.chars().rev().collect();
+    let (tail_reversed, _) = to_target_length(reversed, width.saturating_sub(4));
+    "...".to_owned() + &tail_reversed.chars().rev().collect::<String>()
+}
+
+fn print_confirm(stdout: &mut Stdout, confirm: &Confirm) {
+    let (max_col, _) = crossterm::terminal::size().unwrap();
+    let inner = (max_col as usize).saturating_sub(4).min(76).saturating_sub(4);
+    let mut lines = vec![confirm.title.clone(), "".to_owned()];
+    for (label, value) in &confirm.rows {
+        lines.push(format!("{}: {}", label, fit_tail(value, inner.saturating_sub(label.len() + 2))));
+    }
+    lines.push("".to_owned());
+    if confirm.need_type {
+        lines.push(format!("Type \"{}\" and [Enter] to remove, [Esc] to cancel", confirm.word));
+        lines.push(format!("> {}", confirm.typed));
+    } else {
+        lines.push("[y] Remove  [n]/[Esc] Cancel".to_owned());
+    }
+    print_box(stdout, &lines, Color::DarkRed, None);
+}
+
+/// The sub-targets of the selected dir with their sizes, the selected row is highlighted.
+fn print_detail(stdout: &mut Stdout, options: &Arc<Mutex<Vec<SelectOptions>>>, selected: usize, row: usize) {
+    let (max_col, _) = crossterm::terminal::size().unwrap();
+    let inner = (max_col as usize).saturating_sub(4).min(76).saturating_sub(4);
+    let guard = options.lock().unwrap();
+    let Some(option) = guard.get(selected) else {
+        return;
+    };
+    let mut lines = vec![fit_tail(&format!("Sub-targets of {}", option.path), inner), "".to_owned()];
+    let name_width = option.subs.iter().map(|s| UnicodeWidthStr::width(&s.name[..])).max().unwrap_or(0);
+    for sub in &option.subs {
+        let padding = name_width - UnicodeWidthStr::width(&sub.name[..]);
+        let dirs = if sub.paths.len() > 1 { format!(" ({} dirs)", sub.paths.len()) } else { "".to_owned() };
+        lines.push(format!("{}{}  {}{}", sub.name, " ".repeat(padding), sub.end, dirs));
+    }
+    lines.push("".to_owned());
+    lines.push("[j]/[k] Select  [Space]/[Enter] Remove  [v]/[Esc] Close".to_owned());
+    print_box(stdout, &lines, Color::DarkBlue, Some(row + 2));
+}
+
+/// Print the lines in a box at the center of the screen. The highlighted line has another background.
+fn print_box(stdout: &mut Stdout, lines: &[String], back_color: Color, highlighted: Option<usize>) {
+    let (max_col, max_row) = crossterm::terminal::size().unwrap();
+    let width = (max_col as usize).saturating_sub(4).min(76);
+    let inner = width.saturating_sub(4);
+    let left = (max_col as usize).saturating_sub(width) / 2;
+    let top = (max_row as usize).saturating_sub(lines.len() + 2) / 2;
+    let border = StyledContent { content: " ".repeat(width), front_color: Color::White, back_color };
+    queue!(stdout, cursor::MoveTo(left as u16, top as u16)).unwrap();
+    border.print(stdout);
+    for (i, line) in lines.iter().enumerate() {
+        let line = fit_tail(line, inner);
+        let padding = inner.saturating_sub(UnicodeWidthStr::width(&line[..]));
+        let line_color = if highlighted == Some(i) { Color::Blue } else { back_color };
+        let content = StyledContent { content: format!("  {}{}  ", line, " ".repeat(padding)), front_color: Color::White, back_color: line_color };
+        queue!(stdout, cursor::MoveTo(left as u16, (top + i + 1) as u16)).unwrap();
+        content.print(stdout);
+    }
+    queue!(stdout, cursor::MoveTo(left as u16, (top + lines.len() + 1) as u16)).unwrap();
+    border.print(stdout);
+    stdout.flush().unwrap();
+}
+
+/// Print the dialogs over the selector, the confirm is over the detail view.
+fn print_dialogs(stdout: &mut Stdout, options: &Arc<Mutex<Vec<SelectOptions>>>, selected: usize, view: &View, confirm: &Option<Confirm>) {
+    if let Some(row) = view.detail {
+        print_detail(stdout, options, selected, row);
+    }
+    if let Some(dialog) = confirm {
+        print_confirm(stdout, dialog);
+    }
+}
+
+fn can_mark(option: &SelectOptions) -> bool {
+    matches!(option.status, SelectStatus::Live | SelectStatus::Searched)
+}
+
+/// Mark all the shown ones matched. It never unmarks, even if only one is matched.
+fn mark(options: &Arc<Mutex<Vec<SelectOptions>>>, view: &View, matched: impl Fn(&SelectOptions) -> bool) {
+    let mut guard = options.lock().unwrap();
+    for i in view.order(&guard) {
+        if can_mark(&guard[i]) && matched(&guard[i]) {
+            guard[i].marked = true;
+        }
+    }
+}
+
+/// Whether the selected one is shown. It is not when the filter matches nothing, then the keys acting on it are ignored.
+fn is_shown(options: &Arc<Mutex<Vec<SelectOptions>>>, view: &View, selected: usize) -> bool {
+    let guard = options.lock().unwrap();
+    view.order(&guard).contains(&selected)
+}
+
+/// Toggle the mark of the selected one, if it is shown.
+fn toggle_mark(options: &Arc<Mutex<Vec<SelectOptions>>>, view: &View, selected: usize) {
+    if !is_shown(options, view, selected) {
+        return;
+    }
+    let mut guard = options.lock().unwrap();
+    if can_mark(&guard[selected]) {
+        guard[selected].marked = !guard[selected].marked;
+    }
+}
+
+fn invert_marks(options: &Arc<Mutex<Vec<SelectOptions>>>, view: &View) {
+    let mut guard = options.lock().unwrap();
+    for i in view.order(&guard) {
+        if can_mark(&guard[i]) {
+            guard[i].marked = !guard[i].marked;
+        }
+    }
+}
+
+fn unmark_all(options: &Arc<Mutex<Vec<SelectOptions>>>) {
+    for option in options.lock().unwrap().iter_mut() {
+        option.marked = false;
+    }
+}
+
+pub fn selector(stdout: &mut Stdout, options: Arc<Mutex<Vec<SelectOptions>>>, selected: &mut usize, view: &mut View, need_refresh: Arc<AtomicBool>, confirm_config: &ConfirmConfig, config_file: Option<&Path>) -> (usize, RendererOperation) {
     execute!(stdout, terminal::EnterAlternateScreen).unwrap();
     terminal::enable_raw_mode().unwrap();
-    let input_bottom_content = StyledContent { content: get_bottom_tips(), front_color: Color::Blue, back_color: Color::Reset };
-    refresh_selector(stdout, options.clone(), selected, &top_content, &input_bottom_content);
-    if let Some(value) = handle_key_event(input, input_bottom_content, stdout, options, selected, top_content, need_refresh) {
+    if let Some(value) = handle_key_event(stdout, options, selected, view, need_refresh, confirm_config, config_file) {
         return value;
     }
 
-    (selected.to_owned(), RendererOperation::NONE)
+    (selected.to_owned(), RendererOperation::None)
 }
 
-fn handle_key_event(mut input: String, mut input_bottom_content: StyledContent, stdout: &mut Stdout, options: Arc<Mutex<Vec<SelectOptions>>>, selected: &mut usize, top_content: StyledContent, need_refresh: Arc<AtomicBool>) -> Option<(usize, RendererOperation)> {
+fn handle_key_event(stdout: &mut Stdout, options: Arc<Mutex<Vec<SelectOptions>>>, selected: &mut usize, view: &mut View, need_refresh: Arc<AtomicBool>, confirm_config: &ConfirmConfig, config_file: Option<&Path>) -> Option<(usize, RendererOperation)> {
+    let top_content = StyledContent { content: get_banner(), front_color: Color::Magenta, back_color: Color::Reset };
+    let mut input = String::new();
+    let mut input_bottom_content = StyledContent { content: get_bottom_tips(), front_color: Color::Blue, back_color: Color::Reset };
     let mut update = true;
+    let mut confirm: Option<Confirm> = None;
+    let mut filtering = false;
+    refresh_selector(stdout, options.clone(), selected, view, &top_content, &input_bottom_content);
+    print_dialogs(stdout, &options, *selected, view, &confirm);
     loop {
         if event::poll(Duration::from_millis(400)).unwrap() {
-            if input.starts_with("-") == false {
+            if let Some(dialog) = confirm.as_mut() {
+                if let Event::Key(ke) = event::read().unwrap() {
+                    if ke.kind == KeyEventKind::Press {
+                        match ke.code {
+                            KeyCode::Esc => confirm = None,
+                            KeyCode::Char('y') | KeyCode::Enter if !dialog.need_type => return Some(dialog.operation.clone()),
+                            KeyCode::Char('n') if !dialog.need_type => confirm = None,
+                            KeyCode::Enter if dialog.typed == dialog.word => return Some(dialog.operation.clone()),
+                            KeyCode::Char(c) if dialog.need_type => dialog.typed.push(c),
+                            KeyCode::Backspace => {
+                                dialog.typed.pop();
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                refresh_selector(stdout, options.clone(), selected, view, &top_content, &input_bottom_content);
+                print_dialogs(stdout, &options, *selected, view, &confirm);
+                continue;
+            }
+            if let Some(row) = view.detail {
+                if let Event::Key(ke) = event::read().unwrap() {
+                    if ke.kind == KeyEventKind::Press {
+                        let count = options.lock().unwrap().get(*selected).map_or(0, |o| o.subs.len());
+                        match ke.code {
+                            KeyCode::Esc | KeyCode::Char('v') | KeyCode::Char('q') => view.detail = None,
+                            KeyCode::Down | KeyCode::Char('j') if count > 0 => view.detail = Some((row + 1) % count),
+                            KeyCode::Up | KeyCode::Char('k') if count > 0 => view.detail = Some((row + count - 1) % count),
+                            KeyCode::Char(' ') | KeyCode::Enter => {
+                                if !confirm_config.enabled {
+                                    return Some((selected.to_owned(), RendererOperation::RemoveSub(row)));
+                                }
+                                confirm = Confirm::sub(&options, *selected, row, confirm_config);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                refresh_selector(stdout, options.clone(), selected, view, &top_content, &input_bottom_content);
+                print_dialogs(stdout, &options, *selected, view, &confirm);
+                continue;
+            }
+            if filtering {
+                if let Event::Key(ke) = event::read().unwrap() {
+                    if ke.kind == KeyEventKind::Press {
+                        match ke.code {
+                            KeyCode::Esc => {
+                                view.filter.clear();
+                                filtering = false;
+                            }
+                            KeyCode::Enter => filtering = false,
+                            KeyCode::Backspace => filtering = view.filter.pop().is_some(),
+                            KeyCode::Down => select_down(stdout, options.clone(), selected, view, 1, &top_content, &input_bottom_content),
+                            KeyCode::Up => select_up(stdout, options.clone(), selected, view, 1, &top_content, &input_bottom_content),
+                            KeyCode::Char(c) => view.filter.push(c),
+                            _ => {}
+                        }
+                    }
+                }
+                input_bottom_content.content = if filtering { format!("/{}", view.filter) } else { get_bottom_tips() };
+                refresh_selector(stdout, options.clone(), selected, view, &top_content, &input_bottom_content);
+                continue;
+            }
+            if !input.starts_with('-') {
                 //When there is not any in Input
                 input_bottom_content.content = get_bottom_tips();
-                match event::read().unwrap() {
-                    Event::Key(ke) => {
-                        if ke.kind == KeyEventKind::Press {
-                            match ke.code {
-                                KeyCode::Right => {
-                                    select_down(stdout, options.clone(), selected, 10, &top_content, &input_bottom_content);
-                                }
-                                KeyCode::Left => select_up(stdout, options.clone(), selected, 10, &top_content, &input_bottom_content),
-                                KeyCode::Char(c) => {
-                                    if c == 'q' {
-                                        return Some((usize::MAX, RendererOperation::SYSTEM));
-                                    } else if c == 'j' {
-                                        select_down(stdout, options.clone(), selected, 1, &top_content, &input_bottom_content);
-                                    } else if c == 'k' {
-                                        select_up(stdout, options.clone(), selected, 1, &top_content, &input_bottom_content);
-                                    } else if c == 'b' {
-                                        select_up(stdout, options.clone(), selected, 10, &top_content, &input_bottom_content)
-                                    } else if c == 'f' {
-                                        select_down(stdout, options.clone(), selected, 10, &top_content, &input_bottom_content);
-                                    } else if c == '-' {
-                                        input += "-";
-                                        input_bottom_content.content = input.clone();
-                                        refresh_selector(stdout, options.clone(), selected, &top_content, &input_bottom_content);
-                                    } else if c == ' ' {
-                                        return Some((selected.to_owned(), RendererOperation::REMOVE));
-                                    }
-                                }
-                                KeyCode::Enter => {
-                                    return Some((selected.to_owned(), RendererOperation::REMOVE));
+                if let Event::Key(ke) = event::read().unwrap() {
+                    if ke.kind == KeyEventKind::Press {
+                        match ke.code {
+                            KeyCode::Right | KeyCode::Char('f') => select_down(stdout, options.clone(), selected, view, 10, &top_content, &input_bottom_content),
+                            KeyCode::Left | KeyCode::Char('b') => select_up(stdout, options.clone(), selected, view, 10, &top_content, &input_bottom_content),
+                            KeyCode::Down | KeyCode::Char('j') => select_down(stdout, options.clone(), selected, view, 1, &top_content, &input_bottom_content),
+                            KeyCode::Up | KeyCode::Char('k') => select_up(stdout, options.clone(), selected, view, 1, &top_content, &input_bottom_content),
+                            KeyCode::Char('q') => {
+                                return Some((usize::MAX, RendererOperation::System));
+                            }
+                            KeyCode::Char('-') => {
+                                input += "-";
+                                input_bottom_content.content = input.clone();
+                                refresh_selector(stdout, options.clone(), selected, view, &top_content, &input_bottom_content);
+                            }
+                            KeyCode::Char(' ') | KeyCode::Enter if is_shown(&options, view, *selected) => {
+                                if !confirm_config.enabled {
+                                    return Some((selected.to_owned(), RendererOperation::Remove));
                                 }
-                                KeyCode::Down => {
-                                    select_down(stdout, options.clone(), selected, 1, &top_content, &input_bottom_content);
+                                confirm = Confirm::new(&options, *selected, confirm_config);
+                            }
+                            KeyCode::Char('m') => {
+                                toggle_mark(&options, view, *selected);
+                                select_down(stdout, options.clone(), selected, view, 1, &top_content, &input_bottom_content);
+                            }
+                            KeyCode::Char('a') => mark(&options, view, |_| true),
+                            KeyCode::Char('r') if is_shown(&options, view, *selected) => {
+                                let prefix = options.lock().unwrap().get(*selected).map(|o| o.prefix.clone());
+                                if let Some(prefix) = prefix {
+                                    mark(&options, view, |o| o.prefix == prefix);
                                 }
-                                KeyCode::Up => {
-                                    select_up(stdout, options.clone(), selected, 1, &top_content, &input_bottom_content);
+                            }
+                            KeyCode::Char('i') => invert_marks(&options, view),
+                            KeyCode::Char('s') => view.sort = view.sort.next(),
+                            KeyCode::Char('/') => {
+                                filtering = true;
+                                input_bottom_content.content = format!("/{}", view.filter);
+                            }
+                            KeyCode::Esc => view.filter.clear(),
+                            KeyCode::Char('u') => unmark_all(&options),
+                            KeyCode::Char('v') if is_shown(&options, view, *selected) && !options.lock().unwrap()[*selected].subs.is_empty() => view.detail = Some(0),
+                            KeyCode::Char('d') => {
+                                if !confirm_config.enabled {
+                                    return Some((0, RendererOperation::RemoveMarked));
                                 }
-                                _ => {}
+                                confirm = Confirm::marked(&options, confirm_config);
                             }
+                            _ => {}
                         }
                     }
-                    _ => {}
                 }
-            } else {
-                if update == true {
-                    match event::read().unwrap() {
-                        Event::Key(ke) => {
-                            if ke.kind == KeyEventKind::Press {
-                                match ke.code {
-                                    KeyCode::Char(c) => {
-                                        input += &c.to_string();
-                                        input_bottom_content.content = input.clone();
-                                        refresh_selector(stdout, options.clone(), selected, &top_content, &input_bottom_content);
-                                    }
-                                    KeyCode::Backspace => {
-                                        if input.len() > 0 {
-                                            input = input[0..input.len() - 1].to_owned();
-                                        }
-                                        input_bottom_content.content = input.clone();
-                                        refresh_selector(stdout, options.clone(), selected, &top_content, &input_bottom_content);
-                                    }
-                                    KeyCode::Enter => {
-                                        if input.contains("-config") {
-                                            if let Some(config_path) = get_default_config_path() {
-                                                open_parent_path(&config_path, stdout, &top_content, &mut update);
-                                            }
-                                        } else if input.contains("-open") {
-                                            let guard = options.lock().unwrap();
-                                            open_parent_path(&guard[*selected].path, stdout, &top_content, &mut update);
-                                        } else if input.contains("-help") {
-                                            print_help(stdout, &top_content);
-                                            update = false
-                                        }
-                                    }
-                                    _ => {}
-                                }
+            } else if update {
+                if let Event::Key(ke) = event::read().unwrap() {
+                    if ke.kind == KeyEventKind::Press {
+                        match ke.code {
+                            KeyCode::Char(c) => {
+                                input += &c.to_string();
+                                input_bottom_content.content = input.clone();
+                                refresh_selector(stdout, options.clone(), selected, view, &top_content, &input_bottom_content);
                             }
-                        }
-                        _ => {}
-                    }
-                } else {
-                    match event::read().unwrap() {
-                        Event::Key(ke) => {
-                            if ke.kind == KeyEventKind::Press {
-                                match ke.code {
-                                    KeyCode::Char(c) => {
-                                        if c == 'q' {
-                                            update = true
-                                        }
+                            KeyCode::Backspace => {
+                                input.pop();
+                                input_bottom_content.content = input.clone();
+                                refresh_selector(stdout, options.clone(), selected, view, &top_content, &input_bottom_content);
+                            }
+                            KeyCode::Enter => {
+                                if input.contains("-config") {
+                                    if let Some(config_path) = config_file.and_then(Path::to_str) {
+                                        open_parent_path(config_path, stdout, &top_content, &mut update);
+                                    }
+                                } else if input.contains("-open") {
+                                    let guard = options.lock().unwrap();
+                                    if let Some(option) = guard.get(*selected) {
+                                        open_parent_path(&option.path, stdout, &top_content, &mut update);
                                     }
-                                    _ => {}
+                                } else if input.contains("-help") {
+                                    print_help(stdout, &top_content);
+                                    update = false
                                 }
                             }
+                            _ => {}
                         }
-                        _ => {}
                     }
                 }
+            } else if let Event::Key(ke) = event::read().unwrap() {
+                if ke.kind == KeyEventKind::Press && ke.code == KeyCode::Char('q') {
+                    update = true
+                }
             }
-            if update == true {
-                refresh_selector(stdout, options.clone(), selected, &top_content, &input_bottom_content)
-            }
-        } else {
-            if need_refresh.load(std::sync::atomic::Ordering::Relaxed) == true && update == true {
-                refresh_selector(stdout, options.clone(), selected, &top_content, &input_bottom_content);
-                need_refresh.swap(false, std::sync::atomic::Ordering::Relaxed);
+            if update {
+                refresh_selector(stdout, options.clone(), selected, view, &top_content, &input_bottom_content);
+                print_dialogs(stdout, &options, *selected, view, &confirm);
             }
+        } else if need_refresh.load(std::sync::atomic::Ordering::Relaxed) && update {
+            refresh_selector(stdout, options.clone(), selected, view, &top_content, &input_bottom_content);
+            print_dialogs(stdout, &options, *selected, view, &confirm);
+            need_refresh.swap(false, std::sync::atomic::Ordering::Relaxed);
         }
     }
 }
@@ -332,9 +664,55 @@ fn print_in_another_screen(stdout: &mut Stdout, top_content: &StyledContent, con
 fn open_parent_path(p: &str,stdout: &mut Stdout, top_content: &StyledContent,update:&mut bool) {
     let path = Path::new(p);
     if let Some(p) = path.parent() {
-        if let Err(_) = open::that(p) {
-            print_error(stdout, &top_content, &format!("Can not open: {}\n", p.to_str().unwrap()));
+        if open::that(p).is_err() {
+            print_error(stdout, top_content, &format!("Can not open: {}\n", p.to_str().unwrap()));
             *update = false
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(content: &FixedContent) -> String {
+        content.prefix.clone() + &content.path + &content.point + &content.tail + &content.space + &content.end
+    }
+
+    #[test]
+    fn short_row_is_padded() {
+        let content = file_fix_length(40, "/a/node_modules", "[node]", "[1d][2Mb]");
+        assert_eq!(UnicodeWidthStr::width(&row(&content)[..]), 40);
+        assert_eq!(content.path, "/a/node_modules");
+    }
+
+    #[test]
+    fn long_row_is_cut_in_the_middle() {
+        let path = "/home/user/projects/some/deeply/nested/project/node_modules";
+        let content = file_fix_length(60, path, "[node]", "[3 days ago][1.2Gb]");
+        assert_eq!(content.end, "[3 days ago][1.2Gb]");
+        assert!(path.starts_with(&content.path) && path.ends_with(&content.tail));
+        assert_eq!(UnicodeWidthStr::width(&row(&content)[..]), 60);
+    }
+
+    #[test]
+    fn long_end_drops_the_git_status_and_the_age() {
+        let path = "/home/user/projects/some/deeply/nested/project/node_modules";
+        let end = "[git* 3 months ago][5 months ago][1.23Gb]";
+        assert_eq!(file_fix_length(70, path, "[node]", end).end, "[5 months ago][1.23Gb]");
+        assert_eq!(file_fix_length(30, path, "[node]", end).end, "[1.23Gb]");
+        let content = file_fix_length(10, path, "[node]", end);
+        assert_eq!(content.prefix, "[node]");
+        assert!(UnicodeWidthStr::width(&content.end[..]) <= 10);
+    }
+
+    #[test]
+    fn head_keeps_the_prefix() {
+        let path = "/home/user/projects/node_modules";
+        for max_col in 0..80 {
+            let content = file_fix_length(max_col, path, "[rust-target]", "[Err Permission denied (os error 13)]");
+            assert!(path.starts_with(&content.path));
+            assert!(path.ends_with(&content.tail));
+        }
+    }
+}